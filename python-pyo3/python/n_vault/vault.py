@@ -14,7 +14,7 @@
 
 import os
 
-from collections.abc import Collection
+from collections.abc import Collection, Mapping
 from dataclasses import dataclass
 from typing import Optional, Union
 
@@ -60,6 +60,7 @@ class Vault:
     Note that initializing this class only saves the optional parameters,
     but does *not* construct an actual vault instance.
     Each method in this class creates its own Vault instance internally in the Rust library.
+    For the same reason, the data key cache only reuses data keys within a single `*_many` call.
     """
 
     def __init__(
@@ -72,6 +73,12 @@ class Vault:
         vault_prefix: str = None,
         vault_region: str = None,
         profile: str = None,
+        local_dir: str = None,
+        local_key: str = None,
+        data_key_cache: bool = False,
+        data_key_cache_max_age: int = None,
+        data_key_cache_max_uses: int = None,
+        data_key_cache_max_bytes: int = None,
     ):
         self.vault_stack = vault_stack
         self.vault_key = vault_key
@@ -81,6 +88,12 @@ class Vault:
         self.vault_prefix = vault_prefix
         self.vault_region = vault_region
         self.profile = profile
+        self.local_dir = local_dir
+        self.local_key = local_key
+        self.data_key_cache = data_key_cache
+        self.data_key_cache_max_age = data_key_cache_max_age
+        self.data_key_cache_max_uses = data_key_cache_max_uses
+        self.data_key_cache_max_bytes = data_key_cache_max_bytes
 
         self.config = nitor_vault_rs.VaultConfig(
             vault_stack=self.vault_stack,
@@ -91,6 +104,12 @@ class Vault:
             profile=self.profile,
             iam_id=self.vault_iam_id,
             iam_secret=self.vault_iam_secret,
+            local_dir=self.local_dir,
+            local_key=self.local_key,
+            data_key_cache=self.data_key_cache,
+            data_key_cache_max_age=self.data_key_cache_max_age,
+            data_key_cache_max_uses=self.data_key_cache_max_uses,
+            data_key_cache_max_bytes=self.data_key_cache_max_bytes,
         )
 
     def all(self) -> str:
@@ -110,9 +129,20 @@ class Vault:
         Delete data for multiple keys.
 
         Takes in a collection of key name strings, such as a `list`, `tuple`, or `set`.
+        Raises an error listing the key names that could not be deleted.
         """
         return nitor_vault_rs.delete_many(sorted(names), self.config)
 
+    def delete_many_with_results(self, names: Collection[str], concurrency: int = 16) -> dict[str, str]:
+        """
+        Delete data for multiple keys without raising on failures.
+
+        Takes in a collection of key name strings, such as a `list`, `tuple`, or `set`.
+        A failure for one key does not stop deleting the others.
+        Returns a dict of the key names that could not be deleted with the error message.
+        """
+        return nitor_vault_rs.delete_many_with_results(sorted(names), self.config, concurrency)
+
     def direct_decrypt(self, encrypted_data: bytes) -> bytes:
         """
         Decrypt data with KMS.
@@ -171,6 +201,17 @@ class Vault:
         """
         return nitor_vault_rs.lookup(name, self.config)
 
+    def lookup_many(
+        self, names: Collection[str], concurrency: int = 16
+    ) -> tuple[dict[str, bytes], dict[str, str]]:
+        """
+        Lookup values for multiple keys concurrently.
+
+        Returns a dict of values by key name,
+        and a dict of the key names that could not be looked up with the error message.
+        """
+        return nitor_vault_rs.lookup_many(list(names), self.config, concurrency)
+
     def stack_status(self) -> CloudFormationStackData:
         """
         Get vault Cloudformation stack status.
@@ -187,6 +228,20 @@ class Vault:
 
         return nitor_vault_rs.store(name, data, self.config)
 
+    def store_many(
+        self, data: Mapping[str, Union[bytes, str]], concurrency: int = 16
+    ) -> dict[str, str]:
+        """
+        Store encrypted values for multiple keys concurrently.
+
+        Returns a dict of the key names that could not be stored with the error message.
+        """
+        values = [
+            (name, value.encode("utf-8") if isinstance(value, str) else value)
+            for name, value in data.items()
+        ]
+        return nitor_vault_rs.store_many(values, self.config, concurrency)
+
     def update(self) -> Union[StackUpdated, CloudFormationStackData]:
         """
         Update the vault Cloudformation stack with the current template.
@@ -1,5 +1,7 @@
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::sync::LazyLock;
+use std::time::Duration;
 
 use pyo3::prelude::*;
 use pyo3::types::{IntoPyDict, PyDict};
@@ -7,8 +9,12 @@ use tokio::runtime::Runtime;
 
 use nitor_vault::cloudformation::CloudFormationStackData;
 use nitor_vault::errors::VaultError;
+use nitor_vault::key_provider::DataKeyCacheLimits;
 use nitor_vault::VaultConfig as RustVaultConfig;
-use nitor_vault::{CreateStackResult, UpdateStackResult, Value, Vault};
+use nitor_vault::{BulkResult, CreateStackResult, UpdateStackResult, Value, Vault};
+
+/// Owned bytes that are converted to Python `bytes` instead of a list.
+type Bytes = Cow<'static, [u8]>;
 
 static RUNTIME: LazyLock<Runtime> =
     LazyLock::new(|| Runtime::new().expect("Failed to start async runtime."));
@@ -32,13 +38,29 @@ pub struct VaultConfig {
     pub iam_id: Option<String>,
     #[pyo3(get, set)]
     pub iam_secret: Option<String>,
+    #[pyo3(get, set)]
+    pub local_dir: Option<String>,
+    #[pyo3(get, set)]
+    pub local_key: Option<String>,
+    /// Reuse data keys within one call.
+    ///
+    /// Every call creates a new `Vault`, so only the keys of a single bulk call share the cache.
+    #[pyo3(get, set)]
+    pub data_key_cache: bool,
+    /// Maximum age of a cached data key in seconds.
+    #[pyo3(get, set)]
+    pub data_key_cache_max_age: Option<u64>,
+    #[pyo3(get, set)]
+    pub data_key_cache_max_uses: Option<u64>,
+    #[pyo3(get, set)]
+    pub data_key_cache_max_bytes: Option<u64>,
 }
 
 #[pymethods]
 impl VaultConfig {
     #[new]
     #[must_use]
-    #[pyo3(signature = (vault_stack=None, region=None, bucket=None, key=None, prefix=None, profile=None, iam_id=None, iam_secret=None))]
+    #[pyo3(signature = (vault_stack=None, region=None, bucket=None, key=None, prefix=None, profile=None, iam_id=None, iam_secret=None, local_dir=None, local_key=None, data_key_cache=false, data_key_cache_max_age=None, data_key_cache_max_uses=None, data_key_cache_max_bytes=None))]
     pub const fn new(
         vault_stack: Option<String>,
         region: Option<String>,
@@ -48,6 +70,12 @@ impl VaultConfig {
         profile: Option<String>,
         iam_id: Option<String>,
         iam_secret: Option<String>,
+        local_dir: Option<String>,
+        local_key: Option<String>,
+        data_key_cache: bool,
+        data_key_cache_max_age: Option<u64>,
+        data_key_cache_max_uses: Option<u64>,
+        data_key_cache_max_bytes: Option<u64>,
     ) -> Self {
         Self {
             vault_stack,
@@ -58,6 +86,12 @@ impl VaultConfig {
             profile,
             iam_id,
             iam_secret,
+            local_dir,
+            local_key,
+            data_key_cache,
+            data_key_cache_max_age,
+            data_key_cache_max_uses,
+            data_key_cache_max_bytes,
         }
     }
 }
@@ -73,37 +107,72 @@ impl From<VaultConfig> for RustVaultConfig {
             profile: config.profile,
             iam_id: config.iam_id,
             iam_secret: config.iam_secret,
+            local_dir: config.local_dir,
+            local_key: config.local_key,
+            data_key_cache: config.data_key_cache.then(|| {
+                let defaults = DataKeyCacheLimits::default();
+                DataKeyCacheLimits {
+                    max_age: config
+                        .data_key_cache_max_age
+                        .map_or(defaults.max_age, Duration::from_secs),
+                    max_uses: config.data_key_cache_max_uses.unwrap_or(defaults.max_uses),
+                    max_bytes: config
+                        .data_key_cache_max_bytes
+                        .unwrap_or(defaults.max_bytes),
+                }
+            }),
+            ignore_env: false,
         }
     }
 }
 
 #[pyfunction()]
-fn delete(name: &str, config: VaultConfig) -> PyResult<()> {
+fn delete(name: &str, config: VaultConfig) -> anyhow::Result<()> {
     RUNTIME.block_on(async {
-        Ok(Vault::from_config(config.into())
+        Vault::from_config(config.into())
             .await
             .map_err(vault_error_to_anyhow)?
             .delete(name)
             .await
-            .map_err(vault_error_to_anyhow)?)
+            .map_err(vault_error_to_anyhow)
     })
 }
 
 #[pyfunction()]
 #[allow(clippy::needless_pass_by_value)]
-fn delete_many(names: Vec<String>, config: VaultConfig) -> PyResult<()> {
+fn delete_many(names: Vec<String>, config: VaultConfig) -> anyhow::Result<()> {
     RUNTIME.block_on(async {
-        Ok(Vault::from_config(config.into())
+        Vault::from_config(config.into())
             .await
             .map_err(vault_error_to_anyhow)?
             .delete_many(&names)
             .await
-            .map_err(vault_error_to_anyhow)?)
+            .map_err(vault_error_to_anyhow)
     })
 }
 
 #[pyfunction()]
-fn direct_decrypt(data: &[u8], config: VaultConfig) -> PyResult<Cow<[u8]>> {
+#[pyo3(signature = (names, config, concurrency=Vault::DEFAULT_CONCURRENCY))]
+#[allow(clippy::needless_pass_by_value)]
+fn delete_many_with_results(
+    names: Vec<String>,
+    config: VaultConfig,
+    concurrency: usize,
+) -> anyhow::Result<HashMap<String, String>> {
+    RUNTIME.block_on(async {
+        let results = Vault::from_config(config.into())
+            .await
+            .map_err(vault_error_to_anyhow)?
+            .delete_many_with_results(&names, concurrency)
+            .await
+            .map_err(vault_error_to_anyhow)?;
+
+        Ok(split_bulk_result(results).1)
+    })
+}
+
+#[pyfunction()]
+fn direct_decrypt(data: &[u8], config: VaultConfig) -> anyhow::Result<Cow<'_, [u8]>> {
     // Returns Cow<[u8]> instead of Vec since that will get mapped to bytes for the Python side
     // https://pyo3.rs/main/conversions/tables#returning-rust-values-to-python
     RUNTIME.block_on(async {
@@ -119,7 +188,7 @@ fn direct_decrypt(data: &[u8], config: VaultConfig) -> PyResult<Cow<[u8]>> {
 }
 
 #[pyfunction()]
-fn direct_encrypt(data: &[u8], config: VaultConfig) -> PyResult<Cow<[u8]>> {
+fn direct_encrypt(data: &[u8], config: VaultConfig) -> anyhow::Result<Cow<'_, [u8]>> {
     RUNTIME.block_on(async {
         let result = Vault::from_config(config.into())
             .await
@@ -133,7 +202,7 @@ fn direct_encrypt(data: &[u8], config: VaultConfig) -> PyResult<Cow<[u8]>> {
 }
 
 #[pyfunction()]
-fn exists(name: &str, config: VaultConfig) -> PyResult<bool> {
+fn exists(name: &str, config: VaultConfig) -> anyhow::Result<bool> {
     RUNTIME.block_on(async {
         let result: bool = Vault::from_config(config.into())
             .await
@@ -147,7 +216,7 @@ fn exists(name: &str, config: VaultConfig) -> PyResult<bool> {
 }
 
 #[pyfunction()]
-fn init(config: VaultConfig) -> PyResult<PyObject> {
+fn init(config: VaultConfig) -> anyhow::Result<PyObject> {
     let result = RUNTIME.block_on(async {
         Vault::init(
             config.vault_stack,
@@ -187,7 +256,7 @@ fn init(config: VaultConfig) -> PyResult<PyObject> {
 }
 
 #[pyfunction()]
-fn list_all(config: VaultConfig) -> PyResult<Vec<String>> {
+fn list_all(config: VaultConfig) -> anyhow::Result<Vec<String>> {
     RUNTIME.block_on(async {
         let result = Vault::from_config(config.into())
             .await
@@ -201,7 +270,7 @@ fn list_all(config: VaultConfig) -> PyResult<Vec<String>> {
 }
 
 #[pyfunction()]
-fn lookup(name: &str, config: VaultConfig) -> PyResult<Cow<[u8]>> {
+fn lookup(name: &str, config: VaultConfig) -> anyhow::Result<Cow<'_, [u8]>> {
     RUNTIME.block_on(async {
         let result: Value = Box::pin(
             Vault::from_config(config.into())
@@ -216,9 +285,35 @@ fn lookup(name: &str, config: VaultConfig) -> PyResult<Cow<[u8]>> {
     })
 }
 
+#[pyfunction()]
+#[pyo3(signature = (names, config, concurrency=Vault::DEFAULT_CONCURRENCY))]
+#[allow(clippy::needless_pass_by_value)]
+fn lookup_many(
+    names: Vec<String>,
+    config: VaultConfig,
+    concurrency: usize,
+) -> anyhow::Result<(HashMap<String, Bytes>, HashMap<String, String>)> {
+    RUNTIME.block_on(async {
+        let results = Box::pin(
+            Vault::from_config(config.into())
+                .await
+                .map_err(vault_error_to_anyhow)?
+                .lookup_many(&names, concurrency),
+        )
+        .await;
+
+        let (values, errors) = split_bulk_result(results);
+        let values = values
+            .into_iter()
+            .map(|(name, value)| (name, Cow::Owned(value.to_bytes())))
+            .collect();
+        Ok((values, errors))
+    })
+}
+
 #[pyfunction]
 /// Run Vault CLI with given args.
-fn run(args: Vec<String>) -> PyResult<()> {
+fn run(args: Vec<String>) -> anyhow::Result<()> {
     RUNTIME.block_on(async {
         nitor_vault::run_cli_with_args(args).await?;
         Ok(())
@@ -226,7 +321,7 @@ fn run(args: Vec<String>) -> PyResult<()> {
 }
 
 #[pyfunction()]
-fn stack_status(config: VaultConfig) -> PyResult<PyObject> {
+fn stack_status(config: VaultConfig) -> anyhow::Result<PyObject> {
     let data = RUNTIME.block_on(async {
         Vault::from_config(config.into())
             .await
@@ -243,21 +338,42 @@ fn stack_status(config: VaultConfig) -> PyResult<PyObject> {
 }
 
 #[pyfunction()]
-fn store(name: &str, value: &[u8], config: VaultConfig) -> PyResult<()> {
+fn store(name: &str, value: &[u8], config: VaultConfig) -> anyhow::Result<()> {
     RUNTIME.block_on(async {
-        Ok(Box::pin(
+        Box::pin(
             Vault::from_config(config.into())
                 .await
                 .map_err(vault_error_to_anyhow)?
                 .store(name, value),
         )
         .await
-        .map_err(vault_error_to_anyhow)?)
+        .map_err(vault_error_to_anyhow)
+    })
+}
+
+#[pyfunction()]
+#[pyo3(signature = (values, config, concurrency=Vault::DEFAULT_CONCURRENCY))]
+#[allow(clippy::needless_pass_by_value)]
+fn store_many(
+    values: Vec<(String, Vec<u8>)>,
+    config: VaultConfig,
+    concurrency: usize,
+) -> anyhow::Result<HashMap<String, String>> {
+    RUNTIME.block_on(async {
+        let results = Box::pin(
+            Vault::from_config(config.into())
+                .await
+                .map_err(vault_error_to_anyhow)?
+                .store_many(&values, concurrency),
+        )
+        .await;
+
+        Ok(split_bulk_result(results).1)
     })
 }
 
 #[pyfunction()]
-fn update(config: VaultConfig) -> PyResult<PyObject> {
+fn update(config: VaultConfig) -> anyhow::Result<PyObject> {
     let result = RUNTIME.block_on(async {
         Vault::from_config(config.into())
             .await
@@ -295,19 +411,39 @@ fn nitor_vault_rs(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<VaultConfig>()?;
     m.add_function(wrap_pyfunction!(delete, m)?)?;
     m.add_function(wrap_pyfunction!(delete_many, m)?)?;
+    m.add_function(wrap_pyfunction!(delete_many_with_results, m)?)?;
     m.add_function(wrap_pyfunction!(direct_decrypt, m)?)?;
     m.add_function(wrap_pyfunction!(direct_encrypt, m)?)?;
     m.add_function(wrap_pyfunction!(exists, m)?)?;
     m.add_function(wrap_pyfunction!(init, m)?)?;
     m.add_function(wrap_pyfunction!(list_all, m)?)?;
     m.add_function(wrap_pyfunction!(lookup, m)?)?;
+    m.add_function(wrap_pyfunction!(lookup_many, m)?)?;
     m.add_function(wrap_pyfunction!(run, m)?)?;
     m.add_function(wrap_pyfunction!(stack_status, m)?)?;
     m.add_function(wrap_pyfunction!(store, m)?)?;
+    m.add_function(wrap_pyfunction!(store_many, m)?)?;
     m.add_function(wrap_pyfunction!(update, m)?)?;
     Ok(())
 }
 
+/// Split bulk operation results to successful values and error messages by key name.
+fn split_bulk_result<T>(results: BulkResult<T>) -> (HashMap<String, T>, HashMap<String, String>) {
+    let mut values = HashMap::new();
+    let mut errors = HashMap::new();
+    for (name, result) in results {
+        match result {
+            Ok(value) => {
+                values.insert(name, value);
+            }
+            Err(error) => {
+                errors.insert(name, error.to_string());
+            }
+        }
+    }
+    (values, errors)
+}
+
 /// Convert `VaultError` to `anyhow::Error`
 fn vault_error_to_anyhow(err: VaultError) -> anyhow::Error {
     err.into()
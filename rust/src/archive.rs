@@ -0,0 +1,191 @@
+use aes_gcm::aead::consts::U12;
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::aes::Aes256;
+use aes_gcm::{AesGcm, KeyInit, Nonce};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::compression::Compression;
+use crate::errors::VaultError;
+use crate::key_provider::KeyProvider;
+use crate::SecretMetadata;
+
+/// Current archive format version.
+const ARCHIVE_VERSION: u32 = 1;
+/// Additional authenticated data for the archive ciphertext.
+const ARCHIVE_AAD: &[u8] = b"nitor-vault-archive-v1";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+/// Decrypted secrets with metadata for backing up and restoring a vault.
+pub struct Archive {
+    pub secrets: Vec<ArchivedSecret>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Single secret in an archive.
+pub struct ArchivedSecret {
+    /// Key name without the vault prefix.
+    pub name: String,
+    /// Decrypted secret value.
+    #[serde(with = "base64_bytes")]
+    pub value: Vec<u8>,
+    pub metadata: SecretMetadata,
+    /// Compression codec the value was stored with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<Compression>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+/// How to handle secrets that already exist when importing an archive.
+pub enum ImportPolicy {
+    /// Keep existing secrets.
+    #[default]
+    Skip,
+    /// Replace existing secrets with the archived ones.
+    Overwrite,
+    /// Replace existing secrets only if the archived secret was updated more recently.
+    Merge,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Key names handled when importing an archive.
+pub struct ImportResult {
+    pub imported: Vec<String>,
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+/// Encrypted archive file contents.
+///
+/// The archive is encrypted with its own AES-GCM data key,
+/// which is stored encrypted with the key provider used for export.
+struct EncryptedArchive {
+    version: u32,
+    alg: String,
+    #[serde(with = "base64_bytes")]
+    key: Vec<u8>,
+    #[serde(with = "base64_bytes")]
+    nonce: Vec<u8>,
+    #[serde(with = "base64_bytes")]
+    data: Vec<u8>,
+}
+
+impl Archive {
+    /// Encrypt archive using a new data key from the given key provider.
+    pub async fn encrypt(&self, keys: &dyn KeyProvider) -> Result<Vec<u8>, VaultError> {
+        let data_key = keys.generate_data_key().await?;
+        let cipher: AesGcm<Aes256, U12> = AesGcm::new_from_slice(&data_key.plaintext)?;
+
+        let mut nonce = [0; 12];
+        rand::thread_rng().fill(&mut nonce);
+        let plaintext = serde_json::to_vec(self)?;
+        let data = cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &plaintext,
+                    aad: ARCHIVE_AAD,
+                },
+            )
+            .map_err(|_| VaultError::CiphertextEncryptionError)?;
+
+        let archive = EncryptedArchive {
+            version: ARCHIVE_VERSION,
+            alg: "AESGCM".to_string(),
+            key: data_key.ciphertext,
+            nonce: nonce.to_vec(),
+            data,
+        };
+        Ok(serde_json::to_vec(&archive)?)
+    }
+
+    /// Decrypt archive with the key provider that was used for export.
+    pub async fn decrypt(archive: &[u8], keys: &dyn KeyProvider) -> Result<Self, VaultError> {
+        let archive: EncryptedArchive =
+            serde_json::from_slice(archive).map_err(|_| VaultError::InvalidArchiveError)?;
+        if archive.version != ARCHIVE_VERSION
+            || archive.alg != "AESGCM"
+            || archive.nonce.len() != 12
+        {
+            return Err(VaultError::InvalidArchiveError);
+        }
+
+        let data_key = keys.decrypt(&archive.key).await?;
+        let cipher: AesGcm<Aes256, U12> = AesGcm::new_from_slice(&data_key)?;
+        let plaintext = cipher
+            .decrypt(
+                Nonce::from_slice(&archive.nonce),
+                Payload {
+                    msg: &archive.data,
+                    aad: ARCHIVE_AAD,
+                },
+            )
+            .map_err(|_| VaultError::NonceDecryptError)?;
+
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+}
+
+/// Serialize bytes as a base64 string.
+mod base64_bytes {
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::engine::general_purpose::STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Archive, ArchivedSecret};
+    use crate::errors::VaultError;
+    use crate::key_provider::LocalKeyProvider;
+    use crate::SecretMetadata;
+
+    fn archive() -> Archive {
+        Archive {
+            secrets: vec![ArchivedSecret {
+                name: "db".to_string(),
+                value: vec![0, 159, 146, 150],
+                metadata: SecretMetadata {
+                    description: Some("binary".to_string()),
+                    ..SecretMetadata::default()
+                },
+                compression: None,
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn encrypt_and_decrypt() {
+        let keys = LocalKeyProvider::generate();
+        let encrypted = archive().encrypt(&keys).await.expect("encrypt failed");
+
+        let decrypted = Archive::decrypt(&encrypted, &keys)
+            .await
+            .expect("decrypt failed");
+        assert_eq!(decrypted, archive());
+    }
+
+    #[tokio::test]
+    async fn decrypt_with_wrong_key() {
+        let encrypted = archive()
+            .encrypt(&LocalKeyProvider::generate())
+            .await
+            .expect("encrypt failed");
+
+        let result = Archive::decrypt(&encrypted, &LocalKeyProvider::generate()).await;
+        assert!(matches!(result, Err(VaultError::LocalKeyDecryptError)));
+
+        let result = Archive::decrypt(b"not an archive", &LocalKeyProvider::generate()).await;
+        assert!(matches!(result, Err(VaultError::InvalidArchiveError)));
+    }
+}
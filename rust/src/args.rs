@@ -1,9 +1,17 @@
+use std::time::{Duration, SystemTime};
+
 use anyhow::{Context, Result};
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
 use clap::{CommandFactory, Parser, Subcommand};
 use clap_complete::Shell;
 use colored::Colorize;
 
-use crate::{cli, Vault};
+use crate::archive::ImportPolicy;
+use crate::cli::OutputFormat;
+use crate::key_provider::DataKeyCacheLimits;
+use crate::{
+    cli, Charset, Compression, DumpFormat, KeyMatcher, SecretMetadata, Vault, VaultConfig,
+};
 
 #[allow(clippy::doc_markdown)]
 #[derive(Parser)]
@@ -47,10 +55,48 @@ struct Args {
     #[arg(long = "profile", name = "PROFILE", env = "AWS_PROFILE")]
     aws_profile: Option<String>,
 
+    /// Use a local directory for storage instead of the S3 bucket
+    #[arg(long = "local-dir", name = "DIR", env = "VAULT_LOCAL_DIR")]
+    local_dir: Option<String>,
+
+    /// Use a local AES-256 master key file for encryption instead of KMS
+    #[arg(long = "local-key", name = "KEYFILE", env = "VAULT_LOCAL_KEY")]
+    local_key: Option<String>,
+
+    /// Cache data keys to reduce KMS calls in bulk operations
+    #[arg(long, env = "VAULT_DATA_KEY_CACHE")]
+    data_key_cache: bool,
+
+    /// Maximum age of a cached data key, for example `5m` [default: 5m]
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, requires = "data_key_cache", hide_short_help = true)]
+    data_key_cache_max_age: Option<Duration>,
+
+    /// Maximum number of values encrypted or decrypted with one cached data key [default: 1000]
+    #[arg(
+        long,
+        value_name = "COUNT",
+        requires = "data_key_cache",
+        hide_short_help = true
+    )]
+    data_key_cache_max_uses: Option<u64>,
+
+    /// Maximum number of bytes encrypted with one cached data key [default: 1073741824]
+    #[arg(
+        long,
+        value_name = "BYTES",
+        requires = "data_key_cache",
+        hide_short_help = true
+    )]
+    data_key_cache_max_bytes: Option<u64>,
+
     /// Suppress additional output and error messages
     #[arg(short, long)]
     quiet: bool,
 
+    /// Output format for command results
+    #[arg(long, value_enum, default_value_t)]
+    output: OutputFormat,
+
     /// Available subcommands
     #[command(subcommand)]
     command: Option<Command>,
@@ -60,14 +106,42 @@ struct Args {
 #[derive(Subcommand)]
 enum Command {
     /// List available secrets
+    ///
+    /// Give a path to list only the keys and nested paths directly under it,
+    /// with the number of keys under each nested path,
+    /// or a glob pattern to list matching keys.
+    /// In glob patterns `*` does not match the `/` separator, while `**` matches nested paths.
+    ///
+    /// Usage examples:
+    /// - `vault ls`
+    /// - `vault ls prod/`
+    /// - `vault ls 'prod/**/db-*'`
+    /// - `vault ls --regex 'db-(main|replica)$'`
+    /// - `vault ls --tag team=backend`
     #[command(
+        verbatim_doc_comment,
         short_flag('a'),
         long_flag("all"),
         visible_alias("a"),
         visible_alias("list"),
         visible_alias("ls")
     )]
-    All {},
+    All {
+        /// Show size, last modified time, ETag and storage format for each key
+        #[arg(short, long)]
+        long: bool,
+
+        /// List only the keys and nested paths directly under this path, or keys matching a glob pattern
+        path: Option<String>,
+
+        /// List keys matching a regex
+        #[arg(long, value_name = "REGEX", conflicts_with = "path")]
+        regex: Option<String>,
+
+        /// List only keys with the given tag, can be given multiple times
+        #[arg(long = "tag", value_name = "KEY=VALUE", value_parser = parse_tag)]
+        tags: Vec<(String, String)>,
+    },
 
     /// Generate shell completions
     ///
@@ -84,11 +158,50 @@ enum Command {
         install: bool,
     },
 
+    /// Copy a secret to a new key name, optionally in another vault.
+    ///
+    /// Secrets are copied without decrypting when both vaults use the same KMS key.
+    ///
+    /// Usage examples:
+    /// - `vault cp "key" "new-key"`
+    /// - `vault cp "key" "key" --to-stack "other-vault" --to-region eu-central-1`
+    #[command(visible_alias("cp"), verbatim_doc_comment)]
+    Copy(CopyArgs),
+
     /// Delete an existing key from the store
-    #[command(short_flag('d'), long_flag("delete"), visible_alias("d"))]
+    ///
+    /// Multiple keys can be deleted with a glob pattern or a regex.
+    /// Matching keys are listed and need to be confirmed before deleting, unless `--force` is used.
+    ///
+    /// Usage examples:
+    /// - `vault delete "key"`
+    /// - `vault delete --match 'dev/**'`
+    /// - `vault delete --regex '^tmp-' --force`
+    #[command(
+        short_flag('d'),
+        long_flag("delete"),
+        visible_alias("d"),
+        verbatim_doc_comment
+    )]
     Delete {
         /// Key name to delete
-        key: String,
+        #[arg(
+            required_unless_present_any = ["pattern", "regex"],
+            conflicts_with_all = ["pattern", "regex"]
+        )]
+        key: Option<String>,
+
+        /// Delete all keys matching a glob pattern
+        #[arg(long = "match", value_name = "PATTERN", conflicts_with = "regex")]
+        pattern: Option<String>,
+
+        /// Delete all keys matching a regex
+        #[arg(long, value_name = "REGEX")]
+        regex: Option<String>,
+
+        /// Delete matching keys without confirmation
+        #[arg(short, long)]
+        force: bool,
     },
 
     /// Print CloudFormation stack parameters for current configuration.
@@ -125,6 +238,91 @@ enum Command {
         outfile: Option<String>,
     },
 
+    /// Compare secrets of two vaults, or two paths in the same vault.
+    ///
+    /// Lists keys that only exist on one side and keys with different values.
+    /// Values are compared by hash and only printed with `--show-values`.
+    /// Vaults are given as `STACK/REGION/PROFILE` like for `sync`,
+    /// and both default to the current vault.
+    ///
+    /// Exits with code 0 if there are no differences,
+    /// code 7 if there are differences
+    /// and with code 1 for other errors.
+    ///
+    /// Usage examples:
+    /// - `vault diff vault-staging vault-prod`
+    /// - `vault diff vault/eu-west-1 vault/eu-central-1 --prefix app/`
+    /// - `vault diff --prefix staging/ --other-prefix prod/`
+    #[command(verbatim_doc_comment)]
+    Diff {
+        /// First vault, defaults to the current vault
+        #[arg(value_name = "VAULT", value_parser = parse_vault_spec)]
+        vault: Option<VaultSpec>,
+
+        /// Second vault, defaults to the current vault
+        #[arg(value_name = "OTHER", value_parser = parse_vault_spec)]
+        other: Option<VaultSpec>,
+
+        /// Only compare keys under this path
+        #[arg(long = "prefix", value_name = "PATH")]
+        path: Option<String>,
+
+        /// Path to compare against in the second vault, defaults to `--prefix`
+        #[arg(long = "other-prefix", value_name = "PATH")]
+        other_path: Option<String>,
+
+        /// Print the values of differing keys
+        #[arg(long)]
+        show_values: bool,
+    },
+
+    /// Output all secrets under a path as one document.
+    ///
+    /// Variable names are formed from the key names by removing the prefix path,
+    /// converting letters to upper case and replacing other characters than letters and digits with `_`.
+    /// Binary values are base64 encoded with a `base64:` prefix.
+    ///
+    /// Usage examples:
+    /// - `vault dump --prefix prod/app/ > .env`
+    /// - `vault dump --prefix prod/app/ --format json -o secrets.json`
+    /// - `eval "$(vault dump --prefix prod/app/ --format export)"`
+    #[command(verbatim_doc_comment)]
+    Dump {
+        /// Only output keys under this path
+        #[arg(long = "prefix", value_name = "PATH")]
+        path: Option<String>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t)]
+        format: DumpFormat,
+
+        /// Optional output file
+        #[arg(short, long, value_name = "filepath")]
+        outfile: Option<String>,
+    },
+
+    /// Edit a secret in a text editor.
+    ///
+    /// The value is decrypted to a private temporary file and opened with `$VISUAL` or `$EDITOR`.
+    /// If the content was changed, it is stored again keeping the existing metadata.
+    /// The temporary file is overwritten and removed afterwards.
+    /// On Linux, the file is created under `/dev/shm` so the plaintext is kept in memory.
+    /// Checks on a best-effort basis that the secret was not changed by someone else while editing,
+    /// but a change made right before saving can still be overwritten.
+    ///
+    /// Usage examples:
+    /// - `vault edit "key"`
+    /// - `EDITOR="code --wait" vault edit "key"`
+    #[command(verbatim_doc_comment)]
+    Edit {
+        /// Key name to edit
+        key: String,
+
+        /// Allow editing binary values
+        #[arg(long)]
+        binary: bool,
+    },
+
     /// Directly encrypt given value
     #[command(short_flag('e'), long_flag("encrypt"), visible_alias("e"))]
     Encrypt {
@@ -154,6 +352,32 @@ enum Command {
         outfile: Option<String>,
     },
 
+    /// Run a command with secrets set as environment variables.
+    ///
+    /// Secrets are looked up concurrently before running the command.
+    /// With `--prefix-env`, all keys under the path are set as variables named after the rest of the key,
+    /// converted to upper case and with other characters than letters and digits replaced by `_`.
+    /// Binary values are base64 encoded.
+    /// The command exits with its own exit code.
+    ///
+    /// Usage examples:
+    /// - `vault exec --env DB_PASSWORD=prod/db/password -- ./server`
+    /// - `vault exec --prefix-env prod/app/ -- ./server --port 8080`
+    #[command(verbatim_doc_comment)]
+    Exec {
+        /// Set variable to the value of the given key, can be given multiple times
+        #[arg(short, long = "env", value_name = "NAME=KEY", value_parser = parse_env)]
+        env: Vec<(String, String)>,
+
+        /// Set variables for all keys under the given path, can be given multiple times
+        #[arg(long = "prefix-env", value_name = "PATH")]
+        prefix_env: Vec<String>,
+
+        /// Command to run with its arguments, given after `--`
+        #[arg(required = true, last = true, value_name = "COMMAND")]
+        command: Vec<String>,
+    },
+
     /// Check if a key exists.
     ///
     /// Exits with code 0 if the key exists,
@@ -165,9 +389,108 @@ enum Command {
         key: String,
     },
 
-    /// Print vault information
+    /// List secrets that have expired or expire soon.
+    ///
+    /// Exits with code 0 if no secrets are listed,
+    /// code 6 if there are expired or expiring secrets
+    /// and with code 1 for other errors.
+    ///
+    /// Usage examples:
+    /// - `vault expiring`
+    /// - `vault expiring --within 30d`
+    #[command(verbatim_doc_comment)]
+    Expiring {
+        /// Also list secrets expiring within the given duration, for example `30d`
+        #[arg(short, long, value_name = "DURATION", value_parser = parse_duration, default_value = "0s")]
+        within: Duration,
+    },
+
+    /// Export secrets to an encrypted archive file.
+    ///
+    /// The archive is encrypted with its own data key,
+    /// which is encrypted with the vault key, or with a separate local backup key file.
+    /// Using a backup key file keeps the archive readable even if the vault key is lost.
+    ///
+    /// Usage examples:
+    /// - `vault export --out backup.vault`
+    /// - `vault export prod/ --out prod.vault --key-file backup.key`
+    #[command(verbatim_doc_comment)]
+    Export {
+        /// Only export keys under this path
+        path: Option<String>,
+
+        /// Output file, defaults to stdout
+        #[arg(short, long, value_name = "FILE")]
+        out: Option<String>,
+
+        /// Encrypt archive with a local backup key file instead of the vault key
+        #[arg(long, value_name = "KEYFILE")]
+        key_file: Option<String>,
+    },
+
+    /// Generate a random secret and store it.
+    ///
+    /// The value is generated with a cryptographically secure random number generator,
+    /// and it is only printed with `--show`.
+    /// The length is the number of characters, words or bytes depending on the character set,
+    /// and defaults to 32, or 8 for words.
+    /// Existing keys are not overwritten without `-w`.
+    ///
+    /// Usage examples:
+    /// - `vault generate "prod/db/password"`
+    /// - `vault generate "prod/app/secret" --length 64 --charset symbols`
+    /// - `vault generate "prod/app/aes-key" --charset bytes --length 32`
+    /// - `vault generate "wifi" --charset words --length 6 --show`
+    #[command(verbatim_doc_comment)]
+    Generate {
+        /// Key name to store the generated value as
+        key: String,
+
+        /// Length of the generated value
+        #[arg(short, long, value_parser = clap::value_parser!(u16).range(1..))]
+        length: Option<u16>,
+
+        /// Characters to use in the generated value
+        #[arg(short, long, value_enum, default_value_t)]
+        charset: Charset,
+
+        /// Print the generated value
+        #[arg(long)]
+        show: bool,
+
+        /// Overwrite existing key
+        #[arg(short = 'w', long)]
+        overwrite: bool,
+    },
+
+    /// Import secrets from an encrypted archive file.
+    ///
+    /// The archive can be imported to the same or a different vault.
+    /// Metadata is restored as is, including timestamps.
+    ///
+    /// Usage examples:
+    /// - `vault import backup.vault`
+    /// - `vault --vaultstack other import prod.vault --key-file backup.key --policy merge`
+    #[command(verbatim_doc_comment)]
+    Import {
+        /// Archive file to import, use '-' for stdin
+        archive: String,
+
+        /// Decrypt archive with a local backup key file instead of the vault key
+        #[arg(long, value_name = "KEYFILE")]
+        key_file: Option<String>,
+
+        /// How to handle keys that already exist
+        #[arg(long, value_enum, default_value_t)]
+        policy: ImportPolicy,
+    },
+
+    /// Print vault information, or metadata for the given key
     #[command(long_flag("info"))]
-    Info {},
+    Info {
+        /// Key name to show metadata for
+        key: Option<String>,
+    },
 
     /// Print AWS user account information.
     ///
@@ -210,16 +533,152 @@ enum Command {
     /// Output secret value for given key
     ///
     /// Note that for binary secret data, the raw bytes will be outputted as is.
-    #[command(short_flag('l'), long_flag("lookup"), visible_alias("l"))]
+    ///
+    /// With a glob pattern or a regex, all matching secrets are outputted as a JSON object
+    /// of key names and values. Binary values are base64 encoded in the JSON output.
+    ///
+    /// For JSON secrets, a single field can be outputted with `--field`,
+    /// using either a top-level key or a JSON Pointer path starting with `/`.
+    /// String fields are outputted as is, other values as JSON.
+    ///
+    /// Usage examples:
+    /// - `vault lookup "key"`
+    /// - `vault lookup --match 'prod/app/*'`
+    /// - `vault lookup db --field password`
+    /// - `vault lookup db --field /replicas/0/host`
+    #[command(
+        short_flag('l'),
+        long_flag("lookup"),
+        visible_alias("l"),
+        verbatim_doc_comment
+    )]
     Lookup {
         /// Key name to lookup
-        key: String,
+        #[arg(
+            required_unless_present_any = ["pattern", "regex"],
+            conflicts_with_all = ["pattern", "regex"]
+        )]
+        key: Option<String>,
+
+        /// Lookup all keys matching a glob pattern
+        #[arg(long = "match", value_name = "PATTERN", conflicts_with = "regex")]
+        pattern: Option<String>,
+
+        /// Lookup all keys matching a regex
+        #[arg(long, value_name = "REGEX")]
+        regex: Option<String>,
+
+        /// Output only the given field of a JSON secret
+        #[arg(long, value_name = "FIELD", conflicts_with_all = ["pattern", "regex"])]
+        field: Option<String>,
 
         /// Optional output file
         #[arg(short, long, value_name = "filepath")]
         outfile: Option<String>,
     },
 
+    /// Re-encrypt secrets stored with the old deprecated encryption method.
+    ///
+    /// Secrets stored by old vault versions can't be read until they are re-encrypted with AES-GCM.
+    ///
+    /// Usage examples:
+    /// - `vault recrypt "key"`
+    /// - `vault recrypt --all`
+    #[command(short_flag('c'), long_flag("recrypt"), verbatim_doc_comment)]
+    Recrypt {
+        /// Key name to re-encrypt
+        #[arg(required_unless_present = "all")]
+        key: Option<String>,
+
+        /// Re-encrypt all secrets using the deprecated encryption method
+        #[arg(short, long, conflicts_with = "key")]
+        all: bool,
+    },
+
+    /// Render a template file with secret values.
+    ///
+    /// Placeholders like `{{ vault "prod/db/password" }}` are replaced with the secret value.
+    /// Values can be modified with filters: `base64`, `json`, `trim` and `default "value"`,
+    /// for example `{{ vault "prod/db/password" | trim | json }}`.
+    /// Binary values need the `base64` filter.
+    /// All referenced secrets are looked up concurrently,
+    /// and all missing keys without a default are listed in the error.
+    ///
+    /// Usage examples:
+    /// - `vault render template.conf -o app.conf`
+    /// - `cat template.conf | vault render - > app.conf`
+    #[command(verbatim_doc_comment)]
+    Render {
+        /// Template file path, use '-' to read from stdin
+        template: String,
+
+        /// Optional output file
+        #[arg(short, long, value_name = "filepath")]
+        outfile: Option<String>,
+    },
+
+    /// Re-encrypt data keys under a new master key.
+    ///
+    /// Uses KMS ReEncrypt for each data key, so the encrypted values are not downloaded or modified.
+    /// Data keys already encrypted under the destination key are skipped,
+    /// so an interrupted run can be resumed by running the same command again.
+    ///
+    /// Usage examples:
+    /// - `vault rewrap --to "arn:aws:kms:eu-west-1:123456789012:key/new-key-id"`
+    /// - `vault rewrap --to "alias/new-vault-key" "team/service/"`
+    /// - `vault rewrap --to "alias/new-vault-key" --start-after "team/service/db-password"`
+    #[command(verbatim_doc_comment)]
+    Rewrap {
+        /// Destination KMS key ID, ARN or alias. Key file path when using a local key.
+        #[arg(long = "to", value_name = "KEY")]
+        destination: String,
+
+        /// Only rewrap keys starting with the given path
+        path: Option<String>,
+
+        /// Skip keys up to and including the given key name
+        #[arg(long, value_name = "KEY")]
+        start_after: Option<String>,
+    },
+
+    /// Move or rename a secret, optionally to another vault.
+    ///
+    /// The source is deleted only after the copied secret has been verified to decrypt.
+    ///
+    /// Usage examples:
+    /// - `vault mv "old-name" "new-name"`
+    /// - `vault mv "key" "key" --to-profile "other-account"`
+    #[command(visible_alias("mv"), verbatim_doc_comment)]
+    Move(CopyArgs),
+
+    /// Set a field in a JSON secret.
+    ///
+    /// The secret is decrypted, the field is updated and the secret is stored again
+    /// with its existing metadata.
+    /// The field is either a top-level key or a JSON Pointer path starting with `/`.
+    /// A missing field is added, but its parent needs to exist.
+    /// Checks on a best-effort basis that the secret was not changed by someone else at the same time,
+    /// but a change made right before saving can still be overwritten.
+    ///
+    /// Usage examples:
+    /// - `vault set-field db password "new-password"`
+    /// - `vault set-field db /replicas/0/port 5433 --json`
+    #[command(verbatim_doc_comment)]
+    SetField {
+        /// Key name of the JSON secret
+        key: String,
+
+        /// Field name or JSON Pointer path
+        field: String,
+
+        /// New value for the field, use '-' for stdin
+        value: String,
+
+        /// Parse the new value as JSON instead of using it as a string
+        #[arg(long)]
+        json: bool,
+    },
+
     /// Store a new key-value pair.
     ///
     /// You can provide the key and value directly, or specify a file to store the contents.
@@ -231,6 +690,8 @@ enum Command {
     /// - Store from a file with filename as key: `vault store --file "path/to/file.txt"`
     /// - Store from stdin: `echo "some data" | vault store "key" --value -`
     /// - Store from stdin: `cat file.zip | vault store "key" --file -`
+    /// - Store with metadata: `vault store "key" "value" --description "Database password" --tag env=prod`
+    /// - Store with expiry: `vault store "key" "value" --ttl 90d`
     #[command(
         short_flag('s'),
         long_flag("store"),
@@ -265,6 +726,103 @@ enum Command {
         /// Overwrite existing key
         #[arg(short = 'w', long)]
         overwrite: bool,
+
+        /// Description for the secret
+        #[arg(long)]
+        description: Option<String>,
+
+        /// Owner of the secret
+        #[arg(long)]
+        owner: Option<String>,
+
+        /// Tag for the secret, can be given multiple times
+        #[arg(long = "tag", value_name = "KEY=VALUE", value_parser = parse_tag)]
+        tags: Vec<(String, String)>,
+
+        /// Expiry date or RFC 3339 timestamp, dates expire at the start of the day in UTC
+        #[arg(long, value_name = "DATE", value_parser = parse_expiry, conflicts_with = "ttl")]
+        expires: Option<DateTime>,
+
+        /// Expire after the given duration, for example `90d`
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        ttl: Option<Duration>,
+
+        /// Check that the value is valid JSON before storing
+        #[arg(long)]
+        json: bool,
+
+        /// Encrypt the file in segments without reading it all into memory.
+        ///
+        /// Values stored this way can't be read by older vault versions.
+        #[arg(long, requires = "file", conflicts_with_all = ["json", "value", "value_argument"])]
+        stream: bool,
+
+        /// Compress the value before encryption, values that don't get smaller are not compressed.
+        ///
+        /// Compressed values can't be read by older vault versions.
+        #[arg(
+            long,
+            value_name = "CODEC",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "zstd",
+            conflicts_with = "stream"
+        )]
+        compress: Option<Compression>,
+    },
+
+    /// Sync secrets from one vault to another.
+    ///
+    /// New and changed secrets are copied to the target vault.
+    /// Values are compared by hash after decrypting.
+    /// Vaults are given as `STACK/REGION/PROFILE`, where trailing parts can be left out
+    /// and empty parts use the current configuration.
+    /// The source defaults to the current vault.
+    /// The target bucket and key are always resolved from the target stack,
+    /// and syncing fails if both resolve to the same bucket and prefix.
+    ///
+    /// Usage examples:
+    /// - `vault sync --to vault-prod --dry-run`
+    /// - `vault sync --from vault/eu-west-1 --to vault/eu-central-1 --prefix app/`
+    /// - `vault sync --from //staging --to //prod --delete-extraneous`
+    #[command(verbatim_doc_comment)]
+    Sync {
+        /// Source vault, defaults to the current vault
+        #[arg(long, value_name = "VAULT", value_parser = parse_vault_spec)]
+        from: Option<VaultSpec>,
+
+        /// Target vault
+        #[arg(long, value_name = "VAULT", value_parser = parse_vault_spec)]
+        to: VaultSpec,
+
+        /// Only sync keys under this path
+        #[arg(long = "prefix", value_name = "PATH")]
+        path: Option<String>,
+
+        /// Only show what would be changed
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Delete keys in the target vault that don't exist in the source vault
+        #[arg(long)]
+        delete_extraneous: bool,
+    },
+
+    /// Show keys as a tree grouped by the `/` separator
+    ///
+    /// Each path shows the number of keys under it.
+    ///
+    /// Usage examples:
+    /// - `vault tree`
+    /// - `vault tree prod/ --depth 1`
+    #[command(verbatim_doc_comment)]
+    Tree {
+        /// Only show keys under this path
+        path: Option<String>,
+
+        /// Maximum depth of nested paths to show
+        #[arg(short, long)]
+        depth: Option<usize>,
     },
 
     /// Update the vault CloudFormation stack.
@@ -289,6 +847,40 @@ enum Command {
     },
 }
 
+#[derive(Debug, Clone, Default)]
+/// Vault stack with optional region and AWS profile.
+struct VaultSpec {
+    stack: Option<String>,
+    region: Option<String>,
+    profile: Option<String>,
+}
+
+#[allow(clippy::doc_markdown)]
+#[derive(clap::Args)]
+struct CopyArgs {
+    /// Key name to copy
+    source: String,
+
+    /// Destination key name
+    destination: String,
+
+    /// Overwrite existing destination key
+    #[arg(short = 'w', long)]
+    overwrite: bool,
+
+    /// Copy to the vault in this CloudFormation stack
+    #[arg(long, value_name = "NAME")]
+    to_stack: Option<String>,
+
+    /// Copy to a vault in this region
+    #[arg(long, value_name = "REGION")]
+    to_region: Option<String>,
+
+    /// Copy to a vault using this AWS profile
+    #[arg(long, value_name = "PROFILE")]
+    to_profile: Option<String>,
+}
+
 #[derive(Subcommand, Debug)]
 enum StackAction {
     #[command(
@@ -368,6 +960,27 @@ pub async fn run_cli() -> Result<()> {
 #[allow(clippy::match_same_arms)]
 #[allow(clippy::too_many_lines)]
 async fn run(args: Args) -> Result<()> {
+    let config = VaultConfig {
+        vault_stack: args.vault_stack.clone(),
+        region: args.region.clone(),
+        bucket: args.bucket.clone(),
+        key: args.key_arn.clone(),
+        prefix: args.prefix.clone(),
+        profile: args.aws_profile.clone(),
+        iam_id: args.iam_id.clone(),
+        iam_secret: args.iam_secret.clone(),
+        local_dir: args.local_dir.clone(),
+        local_key: args.local_key.clone(),
+        data_key_cache: args.data_key_cache.then(|| {
+            let defaults = DataKeyCacheLimits::default();
+            DataKeyCacheLimits {
+                max_age: args.data_key_cache_max_age.unwrap_or(defaults.max_age),
+                max_uses: args.data_key_cache_max_uses.unwrap_or(defaults.max_uses),
+                max_bytes: args.data_key_cache_max_bytes.unwrap_or(defaults.max_bytes),
+            }
+        }),
+        ignore_env: false,
+    };
     if let Some(command) = args.command {
         match command {
             Command::Init { name } => {
@@ -379,25 +992,20 @@ async fn run(args: Args) -> Result<()> {
                     args.iam_id,
                     args.iam_secret,
                     args.quiet,
+                    args.output,
                 )
                 .await
                 .with_context(|| "Vault stack initialization failed".red())?;
             }
             Command::Update { name } => {
-                let vault = Vault::new(
-                    args.vault_stack.or(name),
-                    args.region,
-                    args.bucket,
-                    args.key_arn,
-                    args.prefix,
-                    args.aws_profile,
-                    args.iam_id,
-                    args.iam_secret,
-                )
+                let vault = Vault::from_config(VaultConfig {
+                    vault_stack: config.vault_stack.or(name),
+                    ..config
+                })
                 .await
                 .with_context(|| "Failed to create vault with given parameters".red())?;
 
-                cli::update_vault_stack(&vault, args.quiet)
+                cli::update_vault_stack(&vault, args.quiet, args.output)
                     .await
                     .with_context(|| "Failed to update vault stack".red())?;
             }
@@ -405,7 +1013,8 @@ async fn run(args: Args) -> Result<()> {
                 cli::generate_shell_completion(shell, Args::command(), install, args.quiet)?;
             }
             Command::Id {} => {
-                cli::print_aws_account_id(args.region, args.aws_profile, args.quiet).await?;
+                cli::print_aws_account_id(args.region, args.aws_profile, args.quiet, args.output)
+                    .await?;
             }
             Command::Stack { action } => match action {
                 Some(StackAction::Delete {
@@ -423,81 +1032,309 @@ async fn run(args: Args) -> Result<()> {
                     .await?;
                 }
                 Some(StackAction::List) => {
-                    cli::list_stacks(args.region, args.aws_profile, args.quiet).await?;
+                    cli::list_stacks(args.region, args.aws_profile, args.quiet, args.output)
+                        .await?;
                 }
                 None => {
-                    let vault = Vault::new(
-                        args.vault_stack,
-                        args.region,
-                        args.bucket,
-                        args.key_arn,
-                        args.prefix,
-                        args.aws_profile,
-                        args.iam_id,
-                        args.iam_secret,
-                    )
-                    .await
-                    .with_context(|| "Failed to create vault with given parameters".red())?;
+                    let vault = Vault::from_config(config)
+                        .await
+                        .with_context(|| "Failed to create vault with given parameters".red())?;
                     let status = vault.stack_status().await?;
-                    if !args.quiet {
+                    if args.output == OutputFormat::Json {
+                        cli::print_json(&status)?;
+                    } else if !args.quiet {
                         println!("{status}");
                     }
                 }
             },
+            Command::Copy(ref copy) | Command::Move(ref copy) => {
+                let is_move = matches!(command, Command::Move(_));
+                let target_config = copy.target_config(&config);
+                let vault = Vault::from_config(config)
+                    .await
+                    .with_context(|| "Failed to create vault with given parameters".red())?;
+                let target = match target_config {
+                    Some(target_config) => Some(
+                        Vault::from_config(target_config)
+                            .await
+                            .with_context(|| "Failed to create target vault".red())?,
+                    ),
+                    None => None,
+                };
+                cli::copy(
+                    &vault,
+                    target.as_ref().unwrap_or(&vault),
+                    &copy.source,
+                    &copy.destination,
+                    is_move,
+                    copy.overwrite,
+                    args.quiet,
+                )
+                .await?;
+            }
+            Command::Sync {
+                from,
+                to,
+                path,
+                dry_run,
+                delete_extraneous,
+            } => {
+                let source_config =
+                    from.map_or_else(|| config.clone(), |from| from.config(&config));
+                let (source, target) = tokio::try_join!(
+                    Box::pin(Vault::from_config(source_config)),
+                    Box::pin(Vault::from_config(to.config(&config)))
+                )
+                .with_context(|| "Failed to create vaults with given parameters".red())?;
+                cli::sync(
+                    &source,
+                    &target,
+                    path.as_deref(),
+                    dry_run,
+                    delete_extraneous,
+                    args.quiet,
+                )
+                .await?;
+            }
+            Command::Diff {
+                vault,
+                other,
+                path,
+                other_path,
+                show_values,
+            } => {
+                let vault_config =
+                    vault.map_or_else(|| config.clone(), |vault| vault.config(&config));
+                let other_config =
+                    other.map_or_else(|| config.clone(), |other| other.config(&config));
+                let (vault, other) = tokio::try_join!(
+                    Box::pin(Vault::from_config(vault_config)),
+                    Box::pin(Vault::from_config(other_config))
+                )
+                .with_context(|| "Failed to create vaults with given parameters".red())?;
+                if !cli::diff(
+                    &vault,
+                    &other,
+                    path.as_deref(),
+                    other_path.as_deref(),
+                    show_values,
+                    args.quiet,
+                )
+                .await?
+                {
+                    drop(vault);
+                    drop(other);
+                    std::process::exit(7);
+                }
+            }
             // All other commands can use the same single Vault
-            Command::All {}
+            Command::All { .. }
             | Command::Decrypt { .. }
             | Command::Delete { .. }
             | Command::Describe {}
+            | Command::Dump { .. }
+            | Command::Edit { .. }
             | Command::Encrypt { .. }
+            | Command::Exec { .. }
             | Command::Exists { .. }
-            | Command::Info {}
+            | Command::Expiring { .. }
+            | Command::Export { .. }
+            | Command::Generate { .. }
+            | Command::Import { .. }
+            | Command::Info { .. }
             | Command::Lookup { .. }
-            | Command::Store { .. } => {
-                let vault = Vault::new(
-                    args.vault_stack,
-                    args.region,
-                    args.bucket,
-                    args.key_arn,
-                    args.prefix,
-                    args.aws_profile,
-                    args.iam_id,
-                    args.iam_secret,
-                )
-                .await
-                .with_context(|| "Failed to create vault with given parameters".red())?;
+            | Command::Recrypt { .. }
+            | Command::Render { .. }
+            | Command::Rewrap { .. }
+            | Command::SetField { .. }
+            | Command::Store { .. }
+            | Command::Tree { .. } => {
+                let vault = Vault::from_config(config)
+                    .await
+                    .with_context(|| "Failed to create vault with given parameters".red())?;
 
                 match command {
-                    Command::All {} => cli::list_all_keys(&vault).await?,
-                    Command::Delete { key } => cli::delete(&vault, &key).await?,
-                    Command::Describe {} => println!("{}", vault.stack_info()),
+                    Command::All {
+                        long,
+                        path,
+                        regex,
+                        tags,
+                    } => match path {
+                        Some(path) if !long && tags.is_empty() && !KeyMatcher::is_glob(&path) => {
+                            cli::list_directory(&vault, &path, args.output).await?;
+                        }
+                        path => {
+                            let matcher = cli::list_matcher(path.as_deref(), regex.as_deref())?;
+                            let tags = tags.into_iter().collect();
+                            if long {
+                                cli::list_all_keys_long(
+                                    &vault,
+                                    matcher.as_ref(),
+                                    &tags,
+                                    args.output,
+                                )
+                                .await?;
+                            } else {
+                                cli::list_all_keys(&vault, matcher.as_ref(), &tags, args.output)
+                                    .await?;
+                            }
+                        }
+                    },
+                    Command::Delete {
+                        key,
+                        pattern,
+                        regex,
+                        force,
+                    } => match (key, cli::key_matcher(pattern.as_deref(), regex.as_deref())?) {
+                        (_, Some(matcher)) => {
+                            cli::delete_matching(&vault, &matcher, force, args.quiet).await?;
+                        }
+                        (Some(key), None) => cli::delete(&vault, &key).await?,
+                        (None, None) => unreachable!(),
+                    },
+                    Command::Describe {} => match args.output {
+                        OutputFormat::Text => println!("{}", vault.stack_info()),
+                        OutputFormat::Json => cli::print_json(&vault.stack_info())?,
+                    },
                     Command::Decrypt {
                         value,
                         file,
                         value_argument,
                         outfile,
                     } => cli::decrypt(&vault, value, value_argument, file, outfile).await?,
+                    Command::Dump {
+                        path,
+                        format,
+                        outfile,
+                    } => cli::dump(&vault, path.as_deref(), format, outfile).await?,
                     Command::Encrypt {
                         value,
                         file,
                         value_argument,
                         outfile,
                     } => cli::encrypt(&vault, value, value_argument, file, outfile).await?,
+                    Command::Edit { key, binary } => {
+                        cli::edit(&vault, &key, binary, args.quiet).await?;
+                    }
+                    Command::Exec {
+                        env,
+                        prefix_env,
+                        command,
+                    } => {
+                        let code = cli::exec(&vault, env, &prefix_env, &command).await?;
+                        drop(vault);
+                        std::process::exit(code);
+                    }
                     Command::Exists { key } => {
-                        if !cli::exists(&vault, &key, args.quiet).await? {
+                        if !cli::exists(&vault, &key, args.quiet, args.output).await? {
                             drop(vault);
                             std::process::exit(5);
                         }
                     }
-                    Command::Info {} => println!("{vault}"),
-                    Command::Lookup { key, outfile } => cli::lookup(&vault, &key, outfile).await?,
+                    Command::Expiring { within } => {
+                        if !cli::expiring(&vault, within, args.quiet).await? {
+                            drop(vault);
+                            std::process::exit(6);
+                        }
+                    }
+                    Command::Export {
+                        path,
+                        out,
+                        key_file,
+                    } => {
+                        cli::export(&vault, path.as_deref(), out, key_file, args.quiet).await?;
+                    }
+                    Command::Generate {
+                        key,
+                        length,
+                        charset,
+                        show,
+                        overwrite,
+                    } => {
+                        cli::generate(&vault, &key, charset, length, show, overwrite, args.quiet)
+                            .await?;
+                    }
+                    Command::Import {
+                        archive,
+                        key_file,
+                        policy,
+                    } => cli::import(&vault, &archive, key_file, policy, args.quiet).await?,
+                    Command::Info { key } => match key {
+                        Some(key) => cli::info(&vault, &key, args.output).await?,
+                        None => cli::vault_info(&vault, args.output)?,
+                    },
+                    Command::Lookup {
+                        key,
+                        pattern,
+                        regex,
+                        field,
+                        outfile,
+                    } => match (key, cli::key_matcher(pattern.as_deref(), regex.as_deref())?) {
+                        (_, Some(matcher)) => {
+                            cli::lookup_matching(&vault, &matcher, outfile).await?;
+                        }
+                        (Some(key), None) => {
+                            cli::lookup(&vault, &key, field.as_deref(), outfile).await?;
+                        }
+                        (None, None) => unreachable!(),
+                    },
+                    Command::Recrypt { key, all } => {
+                        if all {
+                            cli::recrypt_all(&vault, args.quiet).await?;
+                        } else if let Some(key) = key {
+                            cli::recrypt(&vault, &key, args.quiet).await?;
+                        }
+                    }
+                    Command::Render { template, outfile } => {
+                        cli::render(&vault, &template, outfile).await?;
+                    }
+                    Command::Rewrap {
+                        destination,
+                        path,
+                        start_after,
+                    } => {
+                        cli::rewrap(
+                            &vault,
+                            &destination,
+                            path.as_deref(),
+                            start_after.as_deref(),
+                            args.quiet,
+                        )
+                        .await?;
+                    }
                     Command::Store {
                         key,
                         value,
                         value_argument,
                         file,
                         overwrite,
+                        description,
+                        owner,
+                        tags,
+                        expires,
+                        ttl,
+                        json,
+                        stream,
+                        compress,
                     } => {
+                        let mut metadata = SecretMetadata {
+                            description,
+                            owner,
+                            tags: tags.into_iter().collect(),
+                            ..SecretMetadata::default()
+                        };
+                        let expires = match (expires, ttl) {
+                            (None, Some(ttl)) => Some(
+                                SystemTime::now()
+                                    .checked_add(ttl)
+                                    .map(DateTime::from)
+                                    .with_context(|| format!("TTL is too long: {ttl:?}").red())?,
+                            ),
+                            (expires, _) => expires,
+                        };
+                        if let Some(expires) = expires {
+                            metadata = metadata.with_expiry(expires);
+                        }
                         cli::store(
                             &vault,
                             key,
@@ -505,14 +1342,233 @@ async fn run(args: Args) -> Result<()> {
                             value_argument,
                             file,
                             overwrite,
+                            metadata,
+                            json,
+                            stream,
+                            compress,
                             args.quiet,
                         )
                         .await?;
                     }
+                    Command::SetField {
+                        key,
+                        field,
+                        value,
+                        json,
+                    } => cli::set_field(&vault, &key, &field, value, json).await?,
+                    Command::Tree { path, depth } => {
+                        cli::print_tree(&vault, path.as_deref(), depth).await?;
+                    }
                     _ => unreachable!(),
                 }
             }
-        };
+        }
     }
     Ok(())
 }
+
+impl CopyArgs {
+    /// Return config for the target vault if it differs from the current vault.
+    fn target_config(&self, config: &VaultConfig) -> Option<VaultConfig> {
+        if self.to_stack.is_none() && self.to_region.is_none() && self.to_profile.is_none() {
+            return None;
+        }
+        let spec = VaultSpec {
+            stack: self.to_stack.clone(),
+            region: self.to_region.clone(),
+            profile: self.to_profile.clone(),
+        };
+        Some(spec.config(config))
+    }
+}
+
+impl VaultSpec {
+    /// Return config for this vault, using the given config for missing values.
+    ///
+    /// The vault bucket and key are resolved from its stack,
+    /// so the bucket, key and local storage of the current vault are not used,
+    /// including the ones from environment variables.
+    fn config(&self, config: &VaultConfig) -> VaultConfig {
+        VaultConfig {
+            vault_stack: self.stack.clone().or_else(|| config.vault_stack.clone()),
+            region: self.region.clone().or_else(|| config.region.clone()),
+            profile: self.profile.clone().or_else(|| config.profile.clone()),
+            prefix: config.prefix.clone(),
+            iam_id: config.iam_id.clone(),
+            iam_secret: config.iam_secret.clone(),
+            data_key_cache: config.data_key_cache,
+            ignore_env: true,
+            ..VaultConfig::default()
+        }
+    }
+}
+
+/// Parse vault given as `STACK/REGION/PROFILE`.
+///
+/// Trailing parts can be left out and empty parts are not set.
+fn parse_vault_spec(spec: &str) -> Result<VaultSpec, String> {
+    let mut parts = spec
+        .split('/')
+        .map(|part| (!part.is_empty()).then(|| part.to_string()));
+    let vault = VaultSpec {
+        stack: parts.next().flatten(),
+        region: parts.next().flatten(),
+        profile: parts.next().flatten(),
+    };
+    if parts.next().is_some() {
+        return Err(format!(
+            "invalid vault '{spec}', expected STACK/REGION/PROFILE"
+        ));
+    }
+    Ok(vault)
+}
+
+/// Parse tag given as `key=value`.
+fn parse_tag(tag: &str) -> Result<(String, String), String> {
+    tag.split_once('=')
+        .filter(|(key, _)| !key.is_empty())
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("invalid tag '{tag}', expected KEY=VALUE"))
+}
+
+/// Parse environment variable given as `NAME=KEY`.
+fn parse_env(variable: &str) -> Result<(String, String), String> {
+    variable
+        .split_once('=')
+        .filter(|(name, key)| !name.is_empty() && !key.is_empty())
+        .map(|(name, key)| (name.to_string(), key.to_string()))
+        .ok_or_else(|| format!("invalid variable '{variable}', expected NAME=KEY"))
+}
+
+/// Parse expiry given as a date or an RFC 3339 timestamp.
+///
+/// Dates expire at the start of the day in UTC.
+fn parse_expiry(expiry: &str) -> Result<DateTime, String> {
+    let timestamp = if expiry.contains('T') {
+        expiry.to_string()
+    } else {
+        format!("{expiry}T00:00:00Z")
+    };
+    DateTime::from_str(&timestamp, DateTimeFormat::DateTime).map_err(|_| {
+        format!(
+            "invalid expiry '{expiry}', expected a date like 2026-12-31 or an RFC 3339 timestamp"
+        )
+    })
+}
+
+/// Parse duration given as a number with a unit, for example `90d`.
+///
+/// Supported units are `s`, `m`, `h`, `d` and `w`.
+fn parse_duration(duration: &str) -> Result<Duration, String> {
+    let error =
+        || format!("invalid duration '{duration}', expected a number with unit s, m, h, d or w");
+    let (number, unit) = duration.split_at(
+        duration
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(duration.len()),
+    );
+    let number: u64 = number.parse().map_err(|_| error())?;
+    let unit_seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(error()),
+    };
+    let parsed = number
+        .checked_mul(unit_seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(error)?;
+    // The duration is added to the current time, which must still be a valid timestamp
+    SystemTime::now()
+        .checked_add(parsed)
+        .and_then(|time| crate::format_timestamp(DateTime::from(time)))
+        .map(|_| parsed)
+        .ok_or_else(|| format!("invalid duration '{duration}', the duration is too long"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{parse_duration, parse_env, parse_expiry, parse_vault_spec};
+    use crate::VaultConfig;
+
+    #[test]
+    fn duration_units() {
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(2 * 3600)));
+        assert_eq!(
+            parse_duration("30d"),
+            Ok(Duration::from_secs(30 * 24 * 3600))
+        );
+        assert!(parse_duration("30").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("1y").is_err());
+        assert!(parse_duration("99999999999999w").is_err());
+        assert!(parse_duration("18446744073709551615s").is_err());
+        assert!(parse_duration("520000w").is_err());
+    }
+
+    #[test]
+    fn expiry_date_or_timestamp() {
+        let date = parse_expiry("2026-12-31").expect("invalid date");
+        let timestamp = parse_expiry("2026-12-31T00:00:00Z").expect("invalid timestamp");
+        assert_eq!(date, timestamp);
+        assert!(parse_expiry("31.12.2026").is_err());
+    }
+
+    #[test]
+    fn env_name_and_key() {
+        assert_eq!(
+            parse_env("DB_PASSWORD=prod/db/password"),
+            Ok(("DB_PASSWORD".to_string(), "prod/db/password".to_string()))
+        );
+        assert!(parse_env("DB_PASSWORD").is_err());
+        assert!(parse_env("=prod/db/password").is_err());
+        assert!(parse_env("DB_PASSWORD=").is_err());
+    }
+
+    #[test]
+    fn vault_spec_parts() {
+        let spec = parse_vault_spec("vault/eu-west-1/prod").expect("invalid vault");
+        assert_eq!(spec.stack.as_deref(), Some("vault"));
+        assert_eq!(spec.region.as_deref(), Some("eu-west-1"));
+        assert_eq!(spec.profile.as_deref(), Some("prod"));
+
+        let spec = parse_vault_spec("//prod").expect("invalid vault");
+        assert_eq!(spec.stack, None);
+        assert_eq!(spec.region, None);
+        assert_eq!(spec.profile.as_deref(), Some("prod"));
+
+        let spec = parse_vault_spec("vault").expect("invalid vault");
+        assert_eq!(spec.stack.as_deref(), Some("vault"));
+        assert_eq!(spec.region, None);
+
+        assert!(parse_vault_spec("a/b/c/d").is_err());
+    }
+
+    #[test]
+    fn vault_spec_does_not_use_current_storage() {
+        // The CLI reads the current bucket and key from the environment variables
+        let current = VaultConfig {
+            vault_stack: Some("vault".to_string()),
+            bucket: Some("current-bucket".to_string()),
+            key: Some("current-key".to_string()),
+            local_dir: Some("/tmp/vault".to_string()),
+            prefix: Some("team/".to_string()),
+            ..VaultConfig::default()
+        };
+        let config = parse_vault_spec("other/eu-central-1")
+            .expect("invalid vault")
+            .config(&current);
+        assert_eq!(config.vault_stack.as_deref(), Some("other"));
+        assert_eq!(config.region.as_deref(), Some("eu-central-1"));
+        assert_eq!(config.prefix.as_deref(), Some("team/"));
+        assert!(config.bucket.is_none());
+        assert!(config.key.is_none());
+        assert!(config.local_dir.is_none());
+        assert!(config.ignore_env);
+    }
+}
@@ -1,14 +1,60 @@
+use std::collections::{BTreeMap, HashMap};
 use std::io::{stdin, stdout, IsTerminal, Write};
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 use anyhow::{anyhow, Context, Result};
 use aws_sdk_cloudformation::types::StackStatus;
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
 use clap::Command;
 use clap_complete::Shell;
 use colored::Colorize;
+use futures::{StreamExt, TryStreamExt};
+use serde::Serialize;
 use tokio::time::Duration;
 
-use crate::{cloudformation, CreateStackResult, UpdateStackResult, Value, Vault};
+use crate::archive::{Archive, ImportPolicy};
+use crate::cloudformation::CloudFormationParams;
+use crate::dump::variable_name;
+use crate::errors::VaultError;
+use crate::key_provider::{KeyProvider, LocalKeyProvider};
+use crate::{
+    cloudformation, BulkResult, Charset, Compression, CreateStackResult, DumpFormat, KeyMatcher,
+    SecretFormat, SecretMetadata, Template, UpdateStackResult, Value, Vault,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+/// Output format for command results.
+pub enum OutputFormat {
+    /// Human-readable text.
+    #[default]
+    Text,
+    /// JSON for scripts and automation.
+    Json,
+}
+
+#[derive(Serialize)]
+/// Vault configuration for JSON output.
+struct VaultInfo<'a> {
+    region: &'a str,
+    #[serde(flatten)]
+    stack: &'a CloudFormationParams,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    prefix: &'a str,
+}
+
+#[derive(Serialize)]
+/// Secret metadata with the key name for JSON output.
+struct KeyInfo<'a> {
+    key: &'a str,
+    #[serde(flatten)]
+    metadata: &'a SecretMetadata,
+}
+
+#[cfg(windows)]
+static DEFAULT_EDITOR: &str = "notepad";
+#[cfg(not(windows))]
+static DEFAULT_EDITOR: &str = "vi";
 
 static WAIT_ANIMATION_DURATION: Duration = Duration::from_millis(500);
 static QUIET_WAIT_DURATION: Duration = Duration::from_secs(1);
@@ -24,10 +70,13 @@ pub async fn init_vault_stack(
     iam_id: Option<String>,
     iam_secret: Option<String>,
     quiet: bool,
+    output: OutputFormat,
 ) -> Result<()> {
-    match Vault::init(stack_name, region, bucket, profile, iam_id, iam_secret).await? {
+    let json = output == OutputFormat::Json;
+    let result = Vault::init(stack_name, region, bucket, profile, iam_id, iam_secret).await?;
+    match &result {
         CreateStackResult::Exists { data } => {
-            if !quiet {
+            if !quiet && !json {
                 println!("{}", "Vault stack already initialized:".bold());
                 println!("{data}");
             }
@@ -43,173 +92,1281 @@ pub async fn init_vault_stack(
             stack_id,
             region,
         } => {
-            if !quiet {
+            if !quiet && !json {
                 println!("Stack created with ID: {stack_id}");
             }
-            let config = aws_config::from_env().region(region).load().await;
-            wait_for_stack_creation_to_finish(&config, &stack_name, quiet).await?;
+            let config = aws_config::from_env().region(region.clone()).load().await;
+            wait_for_stack_creation_to_finish(&config, stack_name, quiet || json).await?;
         }
     }
+    if json {
+        print_json(&result)?;
+    }
     Ok(())
 }
 
 /// Update existing Cloudformation vault stack and wait for update to finish.
-pub async fn update_vault_stack(vault: &Vault, quiet: bool) -> Result<()> {
-    match vault
+pub async fn update_vault_stack(vault: &Vault, quiet: bool, output: OutputFormat) -> Result<()> {
+    let json = output == OutputFormat::Json;
+    let result = vault
         .update_stack()
         .await
-        .with_context(|| "Failed to update vault stack".red())?
-    {
+        .with_context(|| "Failed to update vault stack".red())?;
+    match &result {
         UpdateStackResult::UpToDate { data } => {
-            if !quiet {
+            if !quiet && !json {
                 println!("{}", "Vault stack is up to date:".bold());
                 println!("{data}");
             }
-            Ok(())
         }
         UpdateStackResult::Updated {
             stack_id,
             previous_version: current_version,
             new_version,
         } => {
+            if !quiet && !json {
+                println!(
+                    "{}",
+                    format!("Updating vault stack from version {current_version} to {new_version}")
+                        .bold()
+                );
+                println!("{stack_id}");
+            }
+            wait_for_stack_update_to_finish(vault, quiet || json).await?;
+        }
+    }
+    if json {
+        print_json(&result)?;
+    }
+    Ok(())
+}
+
+/// Store a key-value pair.
+#[allow(clippy::fn_params_excessive_bools)]
+pub async fn store(
+    vault: &Vault,
+    key: Option<String>,
+    value_positional: Option<String>,
+    value_argument: Option<String>,
+    file: Option<String>,
+    overwrite: bool,
+    metadata: SecretMetadata,
+    validate_json: bool,
+    stream: bool,
+    compression: Option<Compression>,
+    quiet: bool,
+) -> Result<()> {
+    let key = {
+        if let Some(key) = key {
+            key
+        } else if let Some(file_name) = &file {
+            if file_name == "-" {
+                anyhow::bail!("Key cannot be empty when reading from stdin".red())
+            }
+            let key = get_filename_from_path(file_name)?;
+            if !quiet {
+                println!("Using filename as key: '{key}'");
+            }
+            key
+        } else {
+            anyhow::bail!(
+                "Empty key and no {} flag provided, provide at least one of these",
+                "--file".yellow().bold()
+            )
+        }
+    };
+
+    if !overwrite
+        && vault
+            .exists(&key)
+            .await
+            .with_context(|| format!("Failed to check if key '{key}' exists").red())?
+    {
+        anyhow::bail!(
+            "Key already exists and no {} flag provided for overwriting",
+            "-w".yellow().bold()
+        )
+    }
+
+    if let Some(path) = file.as_deref().filter(|_| stream) {
+        return store_stream(vault, &key, path, metadata).await;
+    }
+
+    let value = read_value(value_positional, value_argument, file)?;
+    if validate_json {
+        value
+            .to_json()
+            .with_context(|| format!("Invalid value for key '{key}'").red())?;
+    }
+
+    let bytes = value.as_bytes();
+    if !overwrite {
+        // The key was checked not to exist, so there is no existing metadata to merge
+        Box::pin(vault.store_new(&key, bytes, metadata, compression)).await
+    } else if let Some(compression) = compression {
+        Box::pin(vault.store_compressed(&key, bytes, metadata, compression)).await
+    } else {
+        Box::pin(vault.store_with_metadata(&key, bytes, metadata)).await
+    }
+    .with_context(|| format!("Failed to store key '{key}'").red())
+}
+
+/// Store a file or stdin with streaming encryption without reading it all into memory.
+async fn store_stream(
+    vault: &Vault,
+    key: &str,
+    path: &str,
+    metadata: SecretMetadata,
+) -> Result<()> {
+    let result = if path == "-" {
+        Box::pin(vault.store_stream(key, tokio::io::stdin(), metadata)).await
+    } else {
+        let file = tokio::fs::File::open(path)
+            .await
+            .with_context(|| format!("Failed to open file '{path}'").red())?;
+        Box::pin(vault.store_stream(key, file, metadata)).await
+    };
+    result.with_context(|| format!("Failed to store key '{key}'").red())
+}
+
+/// Print metadata for the given key.
+pub async fn info(vault: &Vault, key: &str, output: OutputFormat) -> Result<()> {
+    if key.trim().is_empty() {
+        anyhow::bail!(format!("Empty key '{key}'").red())
+    }
+
+    let metadata = vault
+        .metadata(key)
+        .await
+        .with_context(|| format!("Failed to get metadata for key '{key}'").red())?;
+
+    match output {
+        OutputFormat::Text => println!("key: {key}\n{metadata}"),
+        OutputFormat::Json => print_json(&KeyInfo {
+            key,
+            metadata: &metadata,
+        })?,
+    }
+    Ok(())
+}
+
+/// Print vault configuration.
+pub fn vault_info(vault: &Vault, output: OutputFormat) -> Result<()> {
+    match output {
+        OutputFormat::Text => println!("{vault}"),
+        OutputFormat::Json => print_json(&VaultInfo {
+            region: vault.region.as_ref(),
+            stack: &vault.cloudformation_params,
+            prefix: &vault.prefix,
+        })?,
+    }
+    Ok(())
+}
+
+/// Print value as pretty JSON.
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Copy or move a secret to a new key name in the target vault.
+pub async fn copy(
+    vault: &Vault,
+    target: &Vault,
+    source: &str,
+    destination: &str,
+    move_source: bool,
+    overwrite: bool,
+    quiet: bool,
+) -> Result<()> {
+    if source.trim().is_empty() || destination.trim().is_empty() {
+        anyhow::bail!("Empty key".red())
+    }
+
+    if !overwrite
+        && target
+            .exists(destination)
+            .await
+            .with_context(|| format!("Failed to check if key '{destination}' exists").red())?
+    {
+        anyhow::bail!(
+            "Key '{destination}' already exists and no {} flag provided for overwriting",
+            "-w".yellow().bold()
+        )
+    }
+
+    if move_source {
+        Box::pin(vault.rename(source, target, destination))
+            .await
+            .with_context(|| format!("Failed to move key '{source}' to '{destination}'").red())?;
+    } else {
+        Box::pin(vault.copy(source, target, destination))
+            .await
+            .with_context(|| format!("Failed to copy key '{source}' to '{destination}'").red())?;
+    }
+
+    if !quiet {
+        let action = if move_source { "Moved" } else { "Copied" };
+        println!(
+            "{}",
+            format!("{action} '{source}' to '{destination}'").green()
+        );
+    }
+
+    Ok(())
+}
+
+/// Compare secrets between two vaults or paths and print the differences.
+///
+/// Values are only printed if requested.
+/// Returns true if there are no differences.
+pub async fn diff(
+    vault: &Vault,
+    other: &Vault,
+    path: Option<&str>,
+    other_path: Option<&str>,
+    show_values: bool,
+    quiet: bool,
+) -> Result<bool> {
+    let path = path.map(directory_path).unwrap_or_default();
+    let other_path = other_path.map_or_else(|| path.clone(), directory_path);
+    let diff = vault
+        .diff(&path, other, &other_path)
+        .await
+        .with_context(|| "Failed to compare vaults".red())?;
+
+    if !quiet {
+        for name in &diff.only_self {
+            println!("{} {name}", "-".red());
+            if show_values {
+                print_diff_value(vault, &format!("{path}{name}"), "-").await?;
+            }
+        }
+        for name in &diff.only_other {
+            println!("{} {name}", "+".green());
+            if show_values {
+                print_diff_value(other, &format!("{other_path}{name}"), "+").await?;
+            }
+        }
+        for name in &diff.changed {
+            println!("{} {name}", "~".yellow());
+            if show_values {
+                print_diff_value(vault, &format!("{path}{name}"), "-").await?;
+                print_diff_value(other, &format!("{other_path}{name}"), "+").await?;
+            }
+        }
+        println!(
+            "{} only in first, {} only in second, {} changed, {} unchanged",
+            diff.only_self.len(),
+            diff.only_other.len(),
+            diff.changed.len(),
+            diff.unchanged.len()
+        );
+    }
+
+    Ok(diff.only_self.is_empty() && diff.only_other.is_empty() && diff.changed.is_empty())
+}
+
+/// Sync new and changed secrets under the given path from the source vault to the target vault.
+///
+/// Fails if both vaults resolve to the same bucket and prefix.
+/// Continues past failing keys and returns an error at the end if any key failed.
+pub async fn sync(
+    source: &Vault,
+    target: &Vault,
+    path: Option<&str>,
+    dry_run: bool,
+    delete_extraneous: bool,
+    quiet: bool,
+) -> Result<()> {
+    if source.shares_storage_with(target) && source.prefix == target.prefix {
+        anyhow::bail!(format!(
+            "Source and target are the same vault: '{}'",
+            target.cloudformation_params.bucket_name
+        )
+        .red());
+    }
+    let path = path.map(directory_path).unwrap_or_default();
+    let diff = source
+        .diff(&path, target, &path)
+        .await
+        .with_context(|| "Failed to compare vaults".red())?;
+    let full_names = |names: Vec<String>| -> Vec<String> {
+        names
+            .into_iter()
+            .map(|name| format!("{path}{name}"))
+            .collect()
+    };
+    let created = full_names(diff.only_self);
+    let changed = full_names(diff.changed);
+    let extraneous = if delete_extraneous {
+        full_names(diff.only_other)
+    } else {
+        Vec::new()
+    };
+
+    if !quiet {
+        for name in &created {
+            println!("{} {name}", "+".green());
+        }
+        for name in &changed {
+            println!("{} {name}", "~".yellow());
+        }
+        for name in &extraneous {
+            println!("{} {name}", "-".red());
+        }
+    }
+
+    let summary = format!(
+        "{} new, {} changed, {} extraneous, {} unchanged",
+        created.len(),
+        changed.len(),
+        extraneous.len(),
+        diff.unchanged.len()
+    );
+    if dry_run {
+        if !quiet {
+            println!("Dry run: {summary}");
+        }
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    for name in created.iter().chain(&changed) {
+        if let Err(error) = Box::pin(source.copy(name, target, name)).await {
+            eprintln!("{}", format!("Failed to sync key '{name}': {error}").red());
+            failed += 1;
+        }
+    }
+    if !extraneous.is_empty() {
+        let results = target
+            .delete_many_with_results(&extraneous, Vault::DEFAULT_CONCURRENCY)
+            .await
+            .with_context(|| "Failed to delete extraneous keys".red())?;
+        failed += print_bulk_errors(&results, "delete");
+    }
+
+    if failed > 0 {
+        anyhow::bail!(format!("Failed to sync {failed} keys").red());
+    }
+    if !quiet {
+        println!("{}", format!("Synced: {summary}").green());
+    }
+
+    Ok(())
+}
+
+/// Delete key value.
+pub async fn delete(vault: &Vault, key: &str) -> Result<()> {
+    if key.trim().is_empty() {
+        anyhow::bail!(format!("Empty key '{key}'").red())
+    }
+    vault
+        .delete(key)
+        .await
+        .with_context(|| format!("Failed to delete key '{key}'").red())
+}
+
+/// Delete all keys matching the given pattern.
+///
+/// Matching keys are listed and confirmed before deleting unless `force` is set.
+pub async fn delete_matching(
+    vault: &Vault,
+    matcher: &KeyMatcher,
+    force: bool,
+    quiet: bool,
+) -> Result<()> {
+    let keys = vault
+        .all_matching(matcher)
+        .await
+        .with_context(|| "Failed to list keys".red())?;
+
+    if keys.is_empty() {
+        if !quiet {
+            println!("No matching keys");
+        }
+        return Ok(());
+    }
+
+    if !quiet && !force {
+        println!("{}", keys.join("\n"));
+        print!(
+            "Are you sure you want to delete {} keys? [y/N]: ",
+            keys.len()
+        );
+        stdout().flush()?;
+
+        let mut input = String::new();
+        stdin().read_line(&mut input)?;
+        if !matches!(input.trim().to_lowercase().as_str(), "y" | "yes") {
+            println!("skipping key deletion");
+            return Ok(());
+        }
+    } else if quiet && !force {
+        anyhow::bail!("Refusing to delete keys without --force");
+    }
+
+    let results = vault
+        .delete_many_with_results(&keys, Vault::DEFAULT_CONCURRENCY)
+        .await
+        .with_context(|| "Failed to delete keys".red())?;
+    let failed = print_bulk_errors(&results, "delete");
+
+    if !quiet {
+        println!(
+            "{}",
+            format!("Deleted {} keys", keys.len() - failed).green()
+        );
+    }
+    if failed > 0 {
+        anyhow::bail!(format!("Failed to delete {failed} keys").red());
+    }
+
+    Ok(())
+}
+
+/// Delete specified vault cloudformation stack
+pub async fn delete_stack(
+    region: Option<String>,
+    profile: Option<String>,
+    name: Option<String>,
+    force: bool,
+    quiet: bool,
+) -> Result<()> {
+    let stack_name = name.context("Stack name is required")?;
+
+    let config = crate::get_aws_config(region, profile).await;
+    let client = aws_sdk_cloudformation::Client::new(&config);
+
+    if !quiet && !force {
+        let stack = cloudformation::get_stack_data(&client, &stack_name).await?;
+        println!("{stack}");
+
+        print!("Are you sure you want to delete this stack? [y/N]: ");
+        stdout().flush()?;
+
+        let mut input = String::new();
+        stdin().read_line(&mut input)?;
+        if !matches!(input.trim().to_lowercase().as_str(), "y" | "yes") {
+            println!("skipping stack deletion");
+            return Ok(());
+        }
+    } else if quiet && !force {
+        anyhow::bail!("Refusing to delete stack without --force");
+    }
+
+    client.delete_stack().stack_name(stack_name).send().await?;
+    println!("Stack deletion initiated");
+
+    Ok(())
+}
+
+/// Get key value.
+pub async fn lookup(
+    vault: &Vault,
+    key: &str,
+    field: Option<&str>,
+    outfile: Option<String>,
+) -> Result<()> {
+    if key.trim().is_empty() {
+        anyhow::bail!(format!("Empty key '{key}'").red())
+    }
+
+    let Some(field) = field else {
+        return lookup_stream(vault, key, outfile).await;
+    };
+
+    let mut buffer = Vec::new();
+    let metadata = Box::pin(vault.lookup_to_writer(key, &mut buffer))
+        .await
+        .with_context(|| format!("Failed to look up key '{key}'").red())?;
+    let result = match Value::new(buffer)
+        .json_field(field)
+        .with_context(|| format!("Failed to get field for key '{key}'").red())?
+    {
+        serde_json::Value::String(string) => Value::Utf8(string),
+        json => Value::Utf8(json.to_string()),
+    };
+
+    warn_if_expired(key, &metadata);
+
+    match resolve_output_file_path(outfile)? {
+        Some(path) => result.output_to_file(&path)?,
+        None => result.output_to_stdout()?,
+    }
+
+    Ok(())
+}
+
+/// Decrypt value to the output file or stdout.
+///
+/// Values stored with streaming encryption are written as they are decrypted.
+/// The output is written to a temporary file next to the output file,
+/// which replaces the output file only if decryption succeeds.
+async fn lookup_stream(vault: &Vault, key: &str, outfile: Option<String>) -> Result<()> {
+    let metadata = match resolve_output_file_path(outfile)? {
+        Some(path) => {
+            let temp_path = temporary_path(&path);
+            let mut options = tokio::fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            options.mode(0o600);
+            let mut file = options.open(&temp_path).await.with_context(|| {
+                format!("Failed to create file '{}'", temp_path.display()).red()
+            })?;
+            let result = Box::pin(vault.lookup_to_writer(key, &mut file)).await;
+            drop(file);
+            let metadata = match result {
+                Ok(metadata) => metadata,
+                Err(error) => {
+                    let _ = tokio::fs::remove_file(&temp_path).await;
+                    return Err(error)
+                        .with_context(|| format!("Failed to look up key '{key}'").red());
+                }
+            };
+            // Keep the permissions of an existing output file
+            if let Ok(metadata) = tokio::fs::metadata(&path).await {
+                let _ = tokio::fs::set_permissions(&temp_path, metadata.permissions()).await;
+            }
+            if let Err(error) = tokio::fs::rename(&temp_path, &path).await {
+                let _ = tokio::fs::remove_file(&temp_path).await;
+                return Err(error)
+                    .with_context(|| format!("Failed to write '{}'", path.display()).red());
+            }
+            metadata
+        }
+        None => Box::pin(vault.lookup_to_writer(key, &mut tokio::io::stdout()))
+            .await
+            .with_context(|| format!("Failed to look up key '{key}'").red())?,
+    };
+
+    warn_if_expired(key, &metadata);
+    Ok(())
+}
+
+/// Print a warning if the key has expired.
+fn warn_if_expired(key: &str, metadata: &SecretMetadata) {
+    if metadata.is_expired() {
+        eprintln!(
+            "{}",
+            format!(
+                "Warning: key '{key}' expired at {}",
+                metadata.expires.as_deref().unwrap_or_default()
+            )
+            .yellow()
+        );
+    }
+}
+
+/// Generate a random value and store it.
+pub async fn generate(
+    vault: &Vault,
+    key: &str,
+    charset: Charset,
+    length: Option<u16>,
+    show: bool,
+    overwrite: bool,
+    quiet: bool,
+) -> Result<()> {
+    if key.trim().is_empty() {
+        anyhow::bail!(format!("Empty key '{key}'").red())
+    }
+    if !overwrite
+        && vault
+            .exists(key)
+            .await
+            .with_context(|| format!("Failed to check if key '{key}' exists").red())?
+    {
+        anyhow::bail!(
+            "Key already exists and no {} flag provided for overwriting",
+            "-w".yellow().bold()
+        )
+    }
+
+    let length = usize::from(length.unwrap_or(if charset == Charset::Words { 8 } else { 32 }));
+    let value = charset.generate(length);
+    let result = if overwrite {
+        Box::pin(vault.store(key, value.as_bytes())).await
+    } else {
+        Box::pin(vault.store_new(key, value.as_bytes(), SecretMetadata::default(), None)).await
+    };
+    result.with_context(|| format!("Failed to store key '{key}'").red())?;
+
+    if show {
+        value.output_to_stdout()?;
+        if matches!(value, Value::Utf8(_)) {
+            println!();
+        }
+    }
+    if !quiet {
+        eprintln!(
+            "{}",
+            format!(
+                "Stored generated value with {:.0} bits of entropy as '{key}'",
+                charset.entropy_bits(length)
+            )
+            .green()
+        );
+    }
+
+    Ok(())
+}
+
+/// Edit secret value in a text editor.
+///
+/// Nothing is stored if the editor fails or the content was not changed.
+/// A trailing newline added by the editor is removed if the original value did not have one.
+pub async fn edit(vault: &Vault, key: &str, binary: bool, quiet: bool) -> Result<()> {
+    let version = vault
+        .version(key)
+        .await
+        .with_context(|| format!("Failed to look up key '{key}'").red())?;
+    let value = Box::pin(vault.lookup(key))
+        .await
+        .with_context(|| format!("Failed to look up key '{key}'").red())?;
+    if matches!(value, Value::Binary(_)) && !binary {
+        anyhow::bail!(
+            format!("Key '{key}' contains binary data, use --binary to edit it anyway").red()
+        );
+    }
+
+    let file = SecretFile::create(key, value.as_bytes())?;
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| DEFAULT_EDITOR.to_string());
+    let mut parts = editor.split_whitespace();
+    let program = parts
+        .next()
+        .ok_or_else(|| anyhow!("Editor command is empty".red()))?;
+    let status = tokio::process::Command::new(program)
+        .args(parts)
+        .arg(&file.path)
+        .status()
+        .await
+        .with_context(|| format!("Failed to run editor '{editor}'").red())?;
+    if !status.success() {
+        anyhow::bail!(format!("Editor '{editor}' exited with {status}, changes not saved").red());
+    }
+
+    let mut edited = std::fs::read(&file.path)
+        .with_context(|| format!("Failed to read '{}'", file.path.display()).red())?;
+    drop(file);
+    if !value.as_bytes().ends_with(b"\n") && edited.ends_with(b"\n") {
+        edited.pop();
+        if edited.ends_with(b"\r") {
+            edited.pop();
+        }
+    }
+    if edited == value.as_bytes() {
+        if !quiet {
+            println!("No changes to key '{key}'");
+        }
+        return Ok(());
+    }
+
+    if vault
+        .version(key)
+        .await
+        .with_context(|| format!("Failed to look up key '{key}'").red())?
+        != version
+    {
+        return Err(VaultError::ConcurrentModificationError(key.to_string()))
+            .with_context(|| "Changes not saved".red());
+    }
+    Box::pin(vault.store_with_metadata(key, &edited, SecretMetadata::default()))
+        .await
+        .with_context(|| format!("Failed to store key '{key}'").red())?;
+    if !quiet {
+        println!("{}", format!("Updated key '{key}'").green());
+    }
+
+    Ok(())
+}
+
+/// Set a field in a JSON secret.
+///
+/// The value is used as a JSON string unless `json` is set.
+pub async fn set_field(
+    vault: &Vault,
+    key: &str,
+    field: &str,
+    value: String,
+    json: bool,
+) -> Result<()> {
+    let value = if value == "-" {
+        Value::from_stdin()?.to_string()
+    } else {
+        value
+    };
+    let value = if json {
+        serde_json::from_str(&value).with_context(|| "New value is not valid JSON".red())?
+    } else {
+        serde_json::Value::String(value)
+    };
+
+    Box::pin(vault.set_field(key, field, value))
+        .await
+        .with_context(|| format!("Failed to set field '{field}' for key '{key}'").red())
+}
+
+/// Output all secrets matching the given pattern as a JSON object.
+///
+/// Binary values are base64 encoded.
+pub async fn lookup_matching(
+    vault: &Vault,
+    matcher: &KeyMatcher,
+    outfile: Option<String>,
+) -> Result<()> {
+    let keys = vault
+        .all_matching(matcher)
+        .await
+        .with_context(|| "Failed to list keys".red())?;
+
+    let mut values = BTreeMap::new();
+    for (key, result) in Box::pin(vault.lookup_many(&keys, Vault::DEFAULT_CONCURRENCY)).await {
+        let value = result.with_context(|| format!("Failed to look up key '{key}'").red())?;
+        values.insert(key, value.to_string());
+    }
+
+    let json = serde_json::to_string_pretty(&values)?;
+    match resolve_output_file_path(outfile)? {
+        Some(path) => write_private_file(&path, json.as_bytes())?,
+        None => println!("{json}"),
+    }
+
+    Ok(())
+}
+
+/// Run command with secrets set as environment variables.
+///
+/// Variables from `--env` override variables from prefixes.
+/// On Unix the vault process is replaced with the command,
+/// so the command receives signals directly and exits with its own exit code.
+/// On other platforms the command is run as a child process and its exit code is returned.
+pub async fn exec(
+    vault: &Vault,
+    env: Vec<(String, String)>,
+    prefix_env: &[String],
+    command: &[String],
+) -> Result<i32> {
+    let mut variables = Vec::new();
+    for path in prefix_env {
+        variables.extend(prefix_variables(vault, path).await?);
+    }
+    variables.extend(env);
+
+    let keys: Vec<String> = variables.iter().map(|(_, key)| key.clone()).collect();
+    let results = Box::pin(vault.lookup_many(&keys, Vault::DEFAULT_CONCURRENCY)).await;
+    let mut values = Vec::with_capacity(variables.len());
+    for ((name, _), (key, result)) in variables.iter().zip(results) {
+        let value =
+            result.with_context(|| format!("Failed to look up key '{key}' for '{name}'").red())?;
+        values.push((name, value.to_string()));
+    }
+
+    let (program, arguments) = command
+        .split_first()
+        .ok_or_else(|| anyhow!("No command given".red()))?;
+    let mut child = std::process::Command::new(program);
+    child.args(arguments).envs(values);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // Only returns if the command could not be executed
+        let error = child.exec();
+        Err(error).with_context(|| format!("Failed to run '{program}'").red())
+    }
+    #[cfg(not(unix))]
+    {
+        let mut child = tokio::process::Command::from(child)
+            .spawn()
+            .with_context(|| format!("Failed to run '{program}'").red())?;
+        loop {
+            tokio::select! {
+                status = child.wait() => {
+                    let status = status.with_context(|| format!("Failed to run '{program}'").red())?;
+                    return Ok(status.code().unwrap_or(1));
+                }
+                // Ctrl-C is also delivered to the child, so let it decide when to exit
+                _ = tokio::signal::ctrl_c() => {}
+            }
+        }
+    }
+}
+
+/// Output all secrets under the given path as one document.
+///
+/// Variable names are formed from the key names with the path removed.
+pub async fn dump(
+    vault: &Vault,
+    path: Option<&str>,
+    format: DumpFormat,
+    outfile: Option<String>,
+) -> Result<()> {
+    let variables = prefix_variables(vault, path.unwrap_or_default()).await?;
+    let mut keys_by_name = BTreeMap::new();
+    for (name, key) in &variables {
+        if let Some(other) = keys_by_name.insert(name, key) {
+            anyhow::bail!(format!(
+                "Keys '{other}' and '{key}' have the same variable name '{name}'"
+            )
+            .red());
+        }
+    }
+
+    let keys: Vec<String> = variables.iter().map(|(_, key)| key.clone()).collect();
+    let results = Box::pin(vault.lookup_many(&keys, Vault::DEFAULT_CONCURRENCY)).await;
+    let mut secrets = BTreeMap::new();
+    for ((name, _), (key, result)) in variables.into_iter().zip(results) {
+        let value = result.with_context(|| format!("Failed to look up key '{key}'").red())?;
+        secrets.insert(name, value);
+    }
+
+    let output = format.format(&secrets)?;
+    if let Some(path) = resolve_output_file_path(outfile)? {
+        write_private_file(&path, output.as_bytes())?;
+    } else {
+        print!("{output}");
+        stdout().flush()?;
+    }
+
+    Ok(())
+}
+
+/// Render template file with secret values.
+///
+/// Use '-' as the template to read from stdin.
+pub async fn render(vault: &Vault, template: &str, outfile: Option<String>) -> Result<()> {
+    let text = if template == "-" {
+        std::io::read_to_string(stdin()).with_context(|| "Failed to read template".red())?
+    } else {
+        std::fs::read_to_string(template)
+            .with_context(|| format!("Failed to read template '{template}'").red())?
+    };
+    let template = Template::parse(&text).with_context(|| "Failed to parse template".red())?;
+
+    let keys: Vec<String> = template
+        .keys()
+        .into_iter()
+        .map(ToString::to_string)
+        .collect();
+    let mut values = HashMap::new();
+    for (key, result) in Box::pin(vault.lookup_many(&keys, Vault::DEFAULT_CONCURRENCY)).await {
+        match result {
+            Ok(value) => {
+                values.insert(key, value);
+            }
+            Err(VaultError::KeyDoesNotExistError) => {}
+            Err(error) => {
+                return Err(error).with_context(|| format!("Failed to look up key '{key}'").red());
+            }
+        }
+    }
+
+    let output = template
+        .render(&values)
+        .with_context(|| "Failed to render template".red())?;
+    if let Some(path) = resolve_output_file_path(outfile)? {
+        write_private_file(&path, output.as_bytes())?;
+    } else {
+        print!("{output}");
+        stdout().flush()?;
+    }
+
+    Ok(())
+}
+
+/// Export secrets under the given path to an encrypted archive.
+pub async fn export(
+    vault: &Vault,
+    path: Option<&str>,
+    out: Option<String>,
+    key_file: Option<String>,
+    quiet: bool,
+) -> Result<()> {
+    let path = path.map(directory_path).unwrap_or_default();
+    let archive = vault
+        .export(&path)
+        .await
+        .with_context(|| "Failed to export secrets".red())?;
+
+    let backup_key = key_file
+        .map(LocalKeyProvider::from_file)
+        .transpose()
+        .with_context(|| "Failed to read backup key".red())?;
+    let keys = backup_key
+        .as_ref()
+        .map_or_else(|| vault.key_provider(), |key| key as &dyn KeyProvider);
+
+    let encrypted = archive
+        .encrypt(keys)
+        .await
+        .with_context(|| "Failed to encrypt archive".red())?;
+
+    match resolve_output_file_path(out)? {
+        Some(file) => {
+            std::fs::write(&file, encrypted)
+                .with_context(|| format!("Failed to write '{}'", file.display()).red())?;
             if !quiet {
                 println!(
                     "{}",
-                    format!("Updating vault stack from version {current_version} to {new_version}")
-                        .bold()
+                    format!(
+                        "Exported {} secrets to '{}'",
+                        archive.secrets.len(),
+                        file.display()
+                    )
+                    .green()
                 );
-                println!("{stack_id}");
             }
-            wait_for_stack_update_to_finish(vault, quiet).await
         }
+        None => stdout().write_all(&encrypted)?,
     }
+
+    Ok(())
 }
 
-/// Store a key-value pair.
-pub async fn store(
+/// Import secrets from an encrypted archive.
+pub async fn import(
     vault: &Vault,
-    key: Option<String>,
-    value_positional: Option<String>,
-    value_argument: Option<String>,
-    file: Option<String>,
-    overwrite: bool,
+    file: &str,
+    key_file: Option<String>,
+    policy: ImportPolicy,
     quiet: bool,
 ) -> Result<()> {
-    let key = {
-        if let Some(key) = key {
-            key
-        } else if let Some(file_name) = &file {
-            if file_name == "-" {
-                anyhow::bail!("Key cannot be empty when reading from stdin".red())
-            }
-            let key = get_filename_from_path(file_name)?;
-            if !quiet {
-                println!("Using filename as key: '{key}'");
-            }
-            key
-        } else {
-            anyhow::bail!(
-                "Empty key and no {} flag provided, provide at least one of these",
-                "--file".yellow().bold()
-            )
-        }
-    };
+    let encrypted = if file == "-" {
+        Value::from_stdin()
+    } else {
+        Value::from_path(file.to_string())
+    }
+    .with_context(|| format!("Failed to read archive '{file}'").red())?
+    .to_bytes();
 
-    let value = read_value(value_positional, value_argument, file)?;
+    let backup_key = key_file
+        .map(LocalKeyProvider::from_file)
+        .transpose()
+        .with_context(|| "Failed to read backup key".red())?;
+    let keys = backup_key
+        .as_ref()
+        .map_or_else(|| vault.key_provider(), |key| key as &dyn KeyProvider);
 
-    if !overwrite
-        && vault
-            .exists(&key)
-            .await
-            .with_context(|| format!("Failed to check if key '{key}' exists").red())?
-    {
-        anyhow::bail!(
-            "Key already exists and no {} flag provided for overwriting",
-            "-w".yellow().bold()
-        )
+    let archive = Archive::decrypt(&encrypted, keys)
+        .await
+        .with_context(|| "Failed to decrypt archive".red())?;
+
+    let result = vault
+        .import(archive, policy)
+        .await
+        .with_context(|| "Failed to import secrets".red())?;
+
+    if !quiet {
+        for name in &result.skipped {
+            println!("{}", format!("Skipped existing key '{name}'").yellow());
+        }
+        println!(
+            "{}",
+            format!(
+                "Imported {} secrets, skipped {}",
+                result.imported.len(),
+                result.skipped.len()
+            )
+            .green()
+        );
     }
 
-    Box::pin(vault.store(&key, value.as_bytes()))
+    Ok(())
+}
+
+/// List secrets that have expired or expire within the given duration.
+///
+/// Returns `true` if no secrets were listed.
+pub async fn expiring(vault: &Vault, within: Duration, quiet: bool) -> Result<bool> {
+    let expiring = vault
+        .all_expiring(within)
         .await
-        .with_context(|| format!("Failed to store key '{key}'").red())
+        .with_context(|| "Failed to list expiring keys".red())?;
+
+    if !quiet {
+        let now = DateTime::from(SystemTime::now());
+        for (name, expires) in &expiring {
+            let time = expires
+                .fmt(DateTimeFormat::DateTime)
+                .unwrap_or_else(|_| "-".to_string());
+            if *expires <= now {
+                println!("{}  {name}  {}", time.red(), "(expired)".red());
+            } else {
+                println!("{}  {name}", time.yellow());
+            }
+        }
+    }
+
+    Ok(expiring.is_empty())
 }
 
-/// Delete key value.
-pub async fn delete(vault: &Vault, key: &str) -> Result<()> {
+/// Re-encrypt a secret using the deprecated encryption method.
+pub async fn recrypt(vault: &Vault, key: &str, quiet: bool) -> Result<()> {
     if key.trim().is_empty() {
         anyhow::bail!(format!("Empty key '{key}'").red())
     }
-    vault
-        .delete(key)
+
+    let recrypted = Box::pin(vault.recrypt(key))
         .await
-        .with_context(|| format!("Failed to delete key '{key}'").red())
+        .with_context(|| format!("Failed to re-encrypt key '{key}'").red())?;
+
+    if !quiet {
+        if recrypted {
+            println!("{}", format!("Re-encrypted key '{key}'").green());
+        } else {
+            println!("Key '{key}' already uses AES-GCM encryption");
+        }
+    }
+
+    Ok(())
 }
 
-/// Delete specified vault cloudformation stack
-pub async fn delete_stack(
-    region: Option<String>,
-    profile: Option<String>,
-    name: Option<String>,
-    force: bool,
-    quiet: bool,
-) -> Result<()> {
-    let stack_name = name.context("Stack name is required")?;
+/// Re-encrypt all secrets using the deprecated encryption method.
+///
+/// Continues past failing keys and returns an error at the end if any key failed.
+pub async fn recrypt_all(vault: &Vault, quiet: bool) -> Result<()> {
+    let keys = vault
+        .all_deprecated()
+        .await
+        .with_context(|| "Failed to list deprecated keys".red())?;
 
-    let config = crate::get_aws_config(region, profile).await;
-    let client = aws_sdk_cloudformation::Client::new(&config);
+    if keys.is_empty() {
+        if !quiet {
+            println!("No keys using the deprecated encryption method");
+        }
+        return Ok(());
+    }
 
-    if !quiet && !force {
-        let stack = cloudformation::get_stack_data(&client, &stack_name).await?;
-        println!("{stack}");
+    let mut failed = 0;
+    for (index, key) in keys.iter().enumerate() {
+        match Box::pin(vault.recrypt(key)).await {
+            Ok(_) => {
+                if !quiet {
+                    println!("[{}/{}] Re-encrypted key '{key}'", index + 1, keys.len());
+                }
+            }
+            Err(error) => {
+                failed += 1;
+                if !quiet {
+                    eprintln!(
+                        "{}",
+                        format!(
+                            "[{}/{}] Failed to re-encrypt key '{key}': {error}",
+                            index + 1,
+                            keys.len()
+                        )
+                        .red()
+                    );
+                }
+            }
+        }
+    }
 
-        print!("Are you sure you want to delete this stack? [y/N]: ");
-        stdout().flush()?;
+    if failed > 0 {
+        anyhow::bail!(format!("Failed to re-encrypt {failed} of {} keys", keys.len()).red())
+    }
 
-        let mut input = String::new();
-        stdin().read_line(&mut input)?;
-        if !matches!(input.trim().to_lowercase().as_str(), "y" | "yes") {
-            println!("skipping stack deletion");
-            return Ok(());
+    if !quiet {
+        println!("{}", format!("Re-encrypted {} keys", keys.len()).green());
+    }
+
+    Ok(())
+}
+
+/// Re-encrypt data keys under the given destination master key.
+///
+/// Continues past failing keys and returns an error at the end if any key failed.
+pub async fn rewrap(
+    vault: &Vault,
+    destination: &str,
+    path: Option<&str>,
+    start_after: Option<&str>,
+    quiet: bool,
+) -> Result<()> {
+    let (mut keys, deprecated) = tokio::try_join!(vault.all(), vault.all_deprecated())
+        .with_context(|| "Failed to list keys".red())?;
+    keys.extend(deprecated);
+    if let Some(path) = path {
+        keys.retain(|key| key.starts_with(path));
+    }
+    if let Some(start) = start_after {
+        keys.retain(|key| key.as_str() > start);
+    }
+    keys.sort();
+    keys.dedup();
+
+    if keys.is_empty() {
+        if !quiet {
+            println!("No keys to rewrap");
         }
-    } else if quiet && !force {
-        anyhow::bail!("Refusing to delete stack without --force");
+        return Ok(());
     }
 
-    client.delete_stack().stack_name(stack_name).send().await?;
-    println!("Stack deletion initiated");
+    let total = keys.len();
+    let mut rewrapped = 0;
+    let mut failed = Vec::new();
+    for (index, key) in keys.iter().enumerate() {
+        match vault.rewrap(key, destination).await {
+            Ok(changed) => {
+                if changed {
+                    rewrapped += 1;
+                }
+                if !quiet {
+                    let status = if changed {
+                        "rewrapped"
+                    } else {
+                        "already rewrapped"
+                    };
+                    println!("[{}/{total}] {status} '{key}'", index + 1);
+                }
+            }
+            Err(error) => {
+                if !quiet {
+                    eprintln!(
+                        "{}",
+                        format!("[{}/{total}] failed '{key}': {error}", index + 1).red()
+                    );
+                }
+                failed.push(key);
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        anyhow::bail!(format!(
+            "Failed to rewrap {} of {total} keys, run the same command again to retry",
+            failed.len()
+        )
+        .red())
+    }
+
+    if !quiet {
+        println!(
+            "{}",
+            format!("Rewrapped {rewrapped} of {total} keys").green()
+        );
+    }
 
     Ok(())
 }
 
-/// Get key value.
-pub async fn lookup(vault: &Vault, key: &str, outfile: Option<String>) -> Result<()> {
-    if key.trim().is_empty() {
-        anyhow::bail!(format!("Empty key '{key}'").red())
+/// List all available keys, or only keys matching the given pattern and tags.
+pub async fn list_all_keys(
+    vault: &Vault,
+    matcher: Option<&KeyMatcher>,
+    tags: &BTreeMap<String, String>,
+    output: OutputFormat,
+) -> Result<()> {
+    let list = match matcher {
+        Some(matcher) => vault.all_matching(matcher).await,
+        None => vault.all().await,
     }
+    .with_context(|| "Failed to list all keys".red())?;
+
+    let list = filter_tagged(vault, list, tags).await?;
+    if output == OutputFormat::Json {
+        print_json(&list)?;
+    } else if !list.is_empty() {
+        println!("{}", list.join("\n"));
+    }
+
+    Ok(())
+}
 
-    let result = Box::pin(vault.lookup(key))
+/// List keys and nested paths directly under the given path.
+///
+/// Nested paths are listed first.
+pub async fn list_directory(vault: &Vault, path: &str, output: OutputFormat) -> Result<()> {
+    let listing = vault
+        .list_directory(&directory_path(path))
         .await
-        .with_context(|| format!("Failed to look up key '{key}'").red())?;
+        .with_context(|| format!("Failed to list path '{path}'").red())?;
 
-    match resolve_output_file_path(outfile)? {
-        Some(path) => result.output_to_file(&path)?,
-        None => result.output_to_stdout()?,
-    };
+    if output == OutputFormat::Json {
+        return print_json(&listing);
+    }
+
+    for directory in listing.directories {
+        println!("{} ({})", directory.path.blue().bold(), directory.count);
+    }
+    for key in listing.keys {
+        println!("{key}");
+    }
 
     Ok(())
 }
 
-/// List all available keys.
-pub async fn list_all_keys(vault: &Vault) -> Result<()> {
-    vault
-        .all()
+/// Print keys under the given path as a tree.
+pub async fn print_tree(vault: &Vault, path: Option<&str>, depth: Option<usize>) -> Result<()> {
+    let path = path.map(directory_path).unwrap_or_default();
+    let tree = vault
+        .tree(&path)
         .await
-        .with_context(|| "Failed to list all keys".red())
-        .map(|list| {
-            if !list.is_empty() {
-                println!("{}", list.join("\n"));
-            }
-        })
+        .with_context(|| "Failed to list keys".red())?;
+
+    print!("{}", tree.display(depth));
+    Ok(())
+}
+
+/// List all keys with storage object information.
+///
+/// Deprecated and incomplete secrets are included and flagged.
+pub async fn list_all_keys_long(
+    vault: &Vault,
+    matcher: Option<&KeyMatcher>,
+    tags: &BTreeMap<String, String>,
+    output: OutputFormat,
+) -> Result<()> {
+    let mut secrets = vault
+        .all_info()
+        .await
+        .with_context(|| "Failed to list all keys".red())?;
+
+    if let Some(matcher) = matcher {
+        secrets.retain(|info| matcher.is_match(&info.name));
+    }
+    if !tags.is_empty() {
+        let names = secrets.iter().map(|info| info.name.clone()).collect();
+        let tagged = filter_tagged(vault, names, tags).await?;
+        secrets.retain(|info| tagged.contains(&info.name));
+    }
+    if output == OutputFormat::Json {
+        return print_json(&secrets);
+    }
+
+    let size_width = secrets
+        .iter()
+        .map(|info| info.size.to_string().len())
+        .max()
+        .unwrap_or(0);
+
+    for info in secrets {
+        let modified = info
+            .last_modified
+            // Drop sub-second precision to keep columns aligned
+            .and_then(|time| {
+                DateTime::from_secs(time.secs())
+                    .fmt(DateTimeFormat::DateTime)
+                    .ok()
+            })
+            .unwrap_or_else(|| "-".to_string());
+        let format = match info.format {
+            SecretFormat::AesGcm => String::new(),
+            SecretFormat::Deprecated => format!("  ({})", info.format).yellow().to_string(),
+            SecretFormat::Incomplete => format!("  ({})", info.format).red().to_string(),
+        };
+        println!(
+            "{:>size_width$}  {modified:<20}  {:<32}  {}{format}",
+            info.size,
+            info.etag.as_deref().unwrap_or("-"),
+            info.name,
+        );
+    }
+
+    Ok(())
 }
 
 /// List all vault stacks.
@@ -217,11 +1374,14 @@ pub async fn list_stacks(
     region: Option<String>,
     profile: Option<String>,
     quiet: bool,
+    output: OutputFormat,
 ) -> Result<()> {
     let config = crate::get_aws_config(region, profile).await;
     let client = aws_sdk_cloudformation::Client::new(&config);
     let stacks = cloudformation::list_stacks(&client).await?;
-    if stacks.is_empty() {
+    if output == OutputFormat::Json {
+        print_json(&stacks)?;
+    } else if stacks.is_empty() {
         if !quiet {
             println!("No vault stacks found");
         }
@@ -254,7 +1414,7 @@ pub async fn list_stacks(
 }
 
 /// Check if key exists.
-pub async fn exists(vault: &Vault, key: &str, quiet: bool) -> Result<bool> {
+pub async fn exists(vault: &Vault, key: &str, quiet: bool, output: OutputFormat) -> Result<bool> {
     if key.trim().is_empty() {
         anyhow::bail!(format!("Empty key: '{key}'").red())
     }
@@ -264,7 +1424,9 @@ pub async fn exists(vault: &Vault, key: &str, quiet: bool) -> Result<bool> {
         .await
         .with_context(|| format!("Failed to check if key '{key}' exists").red())?;
 
-    if !quiet {
+    if output == OutputFormat::Json {
+        print_json(&serde_json::json!({ "key": key, "exists": exists }))?;
+    } else if !quiet {
         if exists {
             println!("key '{key}' exists");
         } else {
@@ -292,7 +1454,7 @@ pub async fn encrypt(
     match resolve_output_file_path(outfile)? {
         Some(path) => value.output_to_file(&path)?,
         None => value.output_to_stdout()?,
-    };
+    }
 
     Ok(())
 }
@@ -314,7 +1476,7 @@ pub async fn decrypt(
     match resolve_output_file_path(outfile)? {
         Some(path) => value.output_to_file(&path)?,
         None => value.output_to_stdout()?,
-    };
+    }
 
     Ok(())
 }
@@ -324,11 +1486,18 @@ pub async fn print_aws_account_id(
     region: Option<String>,
     profile: Option<String>,
     quiet: bool,
+    output: OutputFormat,
 ) -> Result<()> {
     let config = crate::get_aws_config(region, profile).await;
     let client = aws_sdk_sts::Client::new(&config);
     let result = client.get_caller_identity().send().await?;
-    if !quiet {
+    if output == OutputFormat::Json {
+        print_json(&serde_json::json!({
+            "user": result.user_id,
+            "account": result.account,
+            "arn": result.arn,
+        }))?;
+    } else if !quiet {
         println!(
             "user: {}\naccount: {}\narn: {}",
             result.user_id.unwrap_or_else(|| "None".to_string()),
@@ -489,6 +1658,31 @@ fn resolve_output_file_path(outfile: Option<String>) -> Result<Option<PathBuf>>
     }
 }
 
+/// Return a unique path for a temporary file in the same directory as the given path.
+///
+/// Renaming within the same directory replaces the original file atomically.
+fn temporary_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}.{:016x}.tmp", rand::random::<u64>()))
+}
+
+/// Write decrypted secrets to a file that is only readable by the current user.
+///
+/// An existing file is truncated and keeps its permissions.
+fn write_private_file(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(contents))
+        .with_context(|| format!("Failed to write '{}'", path.display()).red())
+}
+
 /// Read value depending on given CLI arguments.
 fn read_value(
     value_positional: Option<String>,
@@ -582,3 +1776,160 @@ fn get_shell_completion_dir(shell: Shell) -> Result<PathBuf> {
     std::fs::create_dir_all(&user_dir)?;
     Ok(user_dir)
 }
+
+/// Keep only keys that have all the given tags.
+///
+/// Reads the metadata for each key, so nothing is read if there are no tags to match.
+async fn filter_tagged(
+    vault: &Vault,
+    keys: Vec<String>,
+    tags: &BTreeMap<String, String>,
+) -> Result<Vec<String>> {
+    if tags.is_empty() {
+        return Ok(keys);
+    }
+
+    futures::stream::iter(keys)
+        .map(|key| async move {
+            let metadata = vault
+                .metadata(&key)
+                .await
+                .with_context(|| format!("Failed to get metadata for key '{key}'").red())?;
+            Ok(metadata.has_tags(tags).then_some(key))
+        })
+        .buffered(Vault::DEFAULT_CONCURRENCY)
+        .try_filter_map(|key| async move { Ok(key) })
+        .try_collect()
+        .await
+}
+
+/// Create key matcher from either a glob pattern or a regex.
+pub fn key_matcher(pattern: Option<&str>, regex: Option<&str>) -> Result<Option<KeyMatcher>> {
+    match (pattern, regex) {
+        (_, Some(regex)) => Ok(Some(KeyMatcher::regex(regex)?)),
+        (Some(pattern), None) => Ok(Some(KeyMatcher::glob(pattern)?)),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Create key matcher for listing keys.
+///
+/// A path without glob special characters matches the keys directly under it.
+pub fn list_matcher(path: Option<&str>, regex: Option<&str>) -> Result<Option<KeyMatcher>> {
+    let pattern = path.map(|path| {
+        if KeyMatcher::is_glob(path) {
+            path.to_string()
+        } else {
+            format!("{}*", globset::escape(&directory_path(path)))
+        }
+    });
+    key_matcher(pattern.as_deref(), regex)
+}
+
+/// Print secret value indented under a diff line.
+async fn print_diff_value(vault: &Vault, name: &str, sign: &str) -> Result<()> {
+    let value = Box::pin(vault.lookup(name))
+        .await
+        .with_context(|| format!("Failed to look up key '{name}'").red())?;
+    let value = value.to_string();
+    for line in value.lines() {
+        println!("    {sign} {line}");
+    }
+    Ok(())
+}
+
+/// Temporary file containing a decrypted secret.
+///
+/// The file is only readable by the current user,
+/// and it is overwritten with zeros and removed when dropped.
+struct SecretFile {
+    path: PathBuf,
+}
+
+impl SecretFile {
+    /// Create file with the given contents.
+    ///
+    /// The file name ends with the last part of the key name,
+    /// so editors can recognize the file type from the extension.
+    fn create(key: &str, contents: &[u8]) -> Result<Self> {
+        let shm = Path::new("/dev/shm");
+        let directory = if cfg!(target_os = "linux") && shm.is_dir() {
+            shm.to_path_buf()
+        } else {
+            std::env::temp_dir()
+        };
+        let name = key.rsplit('/').next().unwrap_or_default().replace(
+            |c: char| !c.is_ascii_alphanumeric() && c != '.' && c != '-' && c != '_',
+            "_",
+        );
+        let path = directory.join(format!("vault-{:016x}-{name}", rand::random::<u64>()));
+
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(&path)
+            .with_context(|| format!("Failed to create '{}'", path.display()).red())?;
+        let secret_file = Self { path };
+        file.write_all(contents)
+            .with_context(|| format!("Failed to write '{}'", secret_file.path.display()).red())?;
+        Ok(secret_file)
+    }
+}
+
+impl Drop for SecretFile {
+    fn drop(&mut self) {
+        // Editors may replace the file, so overwrite whatever is there now
+        if let Ok(metadata) = std::fs::metadata(&self.path) {
+            if let Ok(mut file) = std::fs::OpenOptions::new().write(true).open(&self.path) {
+                #[allow(clippy::cast_possible_truncation)]
+                let zeros = vec![0; metadata.len() as usize];
+                let _ = file.write_all(&zeros).and_then(|()| file.sync_all());
+            }
+        }
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Print errors from bulk operation results and return the number of failed keys.
+fn print_bulk_errors<T>(results: &BulkResult<T>, action: &str) -> usize {
+    results
+        .iter()
+        .filter_map(|(name, result)| result.as_ref().err().map(|error| (name, error)))
+        .inspect(|(name, error)| {
+            eprintln!(
+                "{}",
+                format!("Failed to {action} key '{name}': {error}").red()
+            );
+        })
+        .count()
+}
+
+/// List all keys under the given path with their variable names.
+///
+/// Returns pairs of variable name and key name.
+async fn prefix_variables(vault: &Vault, path: &str) -> Result<Vec<(String, String)>> {
+    let path = directory_path(path);
+    let matcher = KeyMatcher::glob(&format!("{}**", globset::escape(&path)))?;
+    let keys = vault
+        .all_matching(&matcher)
+        .await
+        .with_context(|| format!("Failed to list keys under '{path}'").red())?;
+    Ok(keys
+        .into_iter()
+        .map(|key| (variable_name(key.strip_prefix(&path).unwrap_or(&key)), key))
+        .collect())
+}
+
+/// Normalize path to end with the `/` separator.
+fn directory_path(path: &str) -> String {
+    if path.is_empty() || path.ends_with('/') {
+        path.to_string()
+    } else {
+        format!("{path}/")
+    }
+}
@@ -2,10 +2,11 @@ use std::fmt;
 
 use aws_sdk_cloudformation::operation::describe_stacks::DescribeStacksOutput;
 use aws_sdk_cloudformation::types::{Output, StackStatus, StackSummary};
+use serde::{Serialize, Serializer};
 
 use crate::errors::VaultError;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 /// Parameter values for Cloudformation resources.
 pub struct CloudFormationParams {
     pub bucket_name: String,
@@ -13,20 +14,23 @@ pub struct CloudFormationParams {
     pub stack_name: String,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 /// Cloudformation stack status information.
 pub struct CloudFormationStackData {
     pub bucket_name: Option<String>,
     pub key_arn: Option<String>,
     pub version: Option<u32>,
+    #[serde(serialize_with = "serialize_status")]
     pub status: Option<StackStatus>,
     pub status_reason: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize)]
 pub struct CloudFormationStackSummary {
     pub stack_name: Option<String>,
     pub stack_id: Option<String>,
     pub template_description: Option<String>,
+    #[serde(serialize_with = "serialize_status")]
     pub stack_status: Option<StackStatus>,
     pub stack_status_reason: Option<String>,
 }
@@ -104,10 +108,11 @@ impl fmt::Display for CloudFormationStackData {
             "status: {}\nbucket: {}\nkey: {}\nversion: {}{}",
             self.status
                 .as_ref()
-                .map_or("None".to_string(), std::string::ToString::to_string),
+                .map_or_else(|| "None".to_string(), std::string::ToString::to_string),
             self.bucket_name.as_deref().unwrap_or("None"),
             self.key_arn.as_deref().unwrap_or("None"),
-            self.version.map_or("None".to_string(), |v| v.to_string()),
+            self.version
+                .map_or_else(|| "None".to_string(), |v| v.to_string()),
             self.status_reason
                 .as_ref()
                 .map_or_else(String::new, |reason| format!("\nreason: {reason}"))
@@ -151,6 +156,18 @@ impl fmt::Display for CloudFormationStackSummary {
     }
 }
 
+/// Serialize stack status as the Cloudformation status string.
+#[allow(clippy::ref_option)]
+fn serialize_status<S: Serializer>(
+    status: &Option<StackStatus>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    status
+        .as_ref()
+        .map(StackStatus::as_str)
+        .serialize(serializer)
+}
+
 /// Extract relevant information from Cloudformation stack outputs
 pub async fn get_stack_data(
     cf_client: &aws_sdk_cloudformation::Client,
@@ -230,7 +247,7 @@ pub async fn list_stacks(
                 .stack_summaries()
                 .iter()
                 .filter(|summary| {
-                    summary.stack_status.as_ref().map_or(false, |status| {
+                    summary.stack_status.as_ref().is_some_and(|status| {
                         *status != StackStatus::DeleteComplete
                             && *status != StackStatus::DeleteInProgress
                     })
@@ -253,3 +270,31 @@ pub async fn list_stacks(
 
     Ok(stacks)
 }
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_cloudformation::types::StackStatus;
+
+    use super::CloudFormationStackData;
+
+    #[test]
+    fn stack_data_json() {
+        let data = CloudFormationStackData {
+            bucket_name: Some("vault-bucket".to_string()),
+            key_arn: None,
+            version: Some(27),
+            status: Some(StackStatus::UpdateComplete),
+            status_reason: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&data).expect("serialize failed"),
+            serde_json::json!({
+                "bucket_name": "vault-bucket",
+                "key_arn": null,
+                "version": 27,
+                "status": "UPDATE_COMPLETE",
+                "status_reason": null,
+            })
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+use crate::errors::VaultError;
+
+/// Algorithm name in the meta object for AES-GCM values compressed with Zstandard.
+///
+/// Compressed values are encrypted with a nonce derived from the stored nonce,
+/// so vault versions without compression support fail to decrypt them
+/// instead of returning the compressed bytes.
+pub const ZSTD_ALGORITHM: &str = "AESGCM-ZSTD";
+
+/// Compression level used for new values.
+///
+/// Decompression speed is about the same for all levels,
+/// so a higher than default level is used to save storage and transfer time.
+const ZSTD_LEVEL: i32 = 9;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+/// Compression codec for values, applied before encryption.
+///
+/// The codec is recorded in the meta object algorithm,
+/// so compressed values are decompressed transparently on lookup.
+pub enum Compression {
+    /// Zstandard compression.
+    #[default]
+    Zstd,
+}
+
+impl Compression {
+    /// Algorithm name in the meta object for values compressed with this codec.
+    pub(crate) const fn algorithm(self) -> &'static str {
+        match self {
+            Self::Zstd => ZSTD_ALGORITHM,
+        }
+    }
+
+    /// Codec for the given meta object algorithm, or `None` if the value is not compressed.
+    pub(crate) fn from_algorithm(algorithm: &str) -> Option<Self> {
+        match algorithm {
+            ZSTD_ALGORITHM => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Compress data with this codec.
+    pub fn compress(self, data: &[u8]) -> Result<Vec<u8>, VaultError> {
+        match self {
+            Self::Zstd => {
+                zstd::bulk::compress(data, ZSTD_LEVEL).map_err(VaultError::CompressionError)
+            }
+        }
+    }
+
+    /// Decompress data compressed with this codec.
+    pub fn decompress(self, data: &[u8]) -> Result<Vec<u8>, VaultError> {
+        match self {
+            Self::Zstd => zstd::stream::decode_all(data).map_err(VaultError::DecompressionError),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Compression;
+
+    #[test]
+    fn zstd_roundtrip() {
+        let data = "apiVersion: v1\nkind: Config\n".repeat(1000);
+        let compressed = Compression::Zstd
+            .compress(data.as_bytes())
+            .expect("compress failed");
+        assert!(compressed.len() < data.len() / 10);
+        assert_eq!(
+            Compression::Zstd
+                .decompress(&compressed)
+                .expect("decompress failed"),
+            data.as_bytes()
+        );
+        assert!(Compression::Zstd.decompress(b"not compressed").is_err());
+    }
+
+    #[test]
+    fn codec_name_in_meta() {
+        assert_eq!(
+            serde_json::to_string(&Compression::Zstd).expect("serialize failed"),
+            r#""zstd""#
+        );
+    }
+}
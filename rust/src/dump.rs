@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use base64::Engine;
+
+use crate::errors::VaultError;
+use crate::Value;
+
+/// Prefix added to base64 encoded binary values.
+pub const BINARY_VALUE_PREFIX: &str = "base64:";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+/// Output format for dumping multiple secrets as one document.
+///
+/// Binary values are base64 encoded with the `base64:` prefix in all formats.
+pub enum DumpFormat {
+    /// `NAME="value"` lines with `\`, `"` and newlines escaped.
+    #[default]
+    Dotenv,
+    /// JSON object.
+    Json,
+    /// YAML mapping with double-quoted values.
+    Yaml,
+    /// Shell `export NAME='value'` lines.
+    Export,
+}
+
+impl DumpFormat {
+    /// Format secrets with the variable name as key.
+    pub fn format(self, secrets: &BTreeMap<String, Value>) -> Result<String, VaultError> {
+        let values = secrets
+            .iter()
+            .map(|(name, value)| (name.as_str(), dump_value(value)));
+
+        let output = match self {
+            Self::Dotenv => values.fold(String::new(), |mut output, (name, value)| {
+                let escaped = value
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('\n', "\\n");
+                let _ = writeln!(output, "{name}=\"{escaped}\"");
+                output
+            }),
+            Self::Json => {
+                let values: BTreeMap<&str, String> = values.collect();
+                let mut output = serde_json::to_string_pretty(&values)?;
+                output.push('\n');
+                output
+            }
+            Self::Yaml => {
+                if secrets.is_empty() {
+                    return Ok("{}\n".to_string());
+                }
+                // JSON strings are valid YAML double-quoted scalars
+                values.fold(String::new(), |mut output, (name, value)| {
+                    let _ = writeln!(output, "{name}: {}", serde_json::Value::String(value));
+                    output
+                })
+            }
+            Self::Export => values.fold(String::new(), |mut output, (name, value)| {
+                let _ = writeln!(output, "export {name}='{}'", value.replace('\'', "'\\''"));
+                output
+            }),
+        };
+        Ok(output)
+    }
+}
+
+#[must_use]
+/// Convert key name to a variable name.
+///
+/// Letters are converted to upper case,
+/// and other characters than ASCII letters and digits are replaced with `_`.
+pub fn variable_name(key: &str) -> String {
+    key.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Return value as a string, with binary data base64 encoded with a prefix.
+fn dump_value(value: &Value) -> String {
+    match value {
+        Value::Utf8(string) => string.clone(),
+        Value::Binary(bytes) => format!(
+            "{BINARY_VALUE_PREFIX}{}",
+            base64::engine::general_purpose::STANDARD.encode(bytes)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{variable_name, DumpFormat};
+    use crate::Value;
+
+    fn secrets() -> BTreeMap<String, Value> {
+        BTreeMap::from([
+            (
+                "DB_PASSWORD".to_string(),
+                Value::Utf8("it's \"quoted\"\nline".to_string()),
+            ),
+            ("CERT".to_string(), Value::Binary(vec![0xff, 0xfe])),
+        ])
+    }
+
+    #[test]
+    fn variable_names() {
+        assert_eq!(variable_name("db/password"), "DB_PASSWORD");
+        assert_eq!(variable_name("api-key.v2"), "API_KEY_V2");
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(
+            DumpFormat::Dotenv
+                .format(&secrets())
+                .expect("format failed"),
+            "CERT=\"base64://4=\"\nDB_PASSWORD=\"it's \\\"quoted\\\"\\nline\"\n"
+        );
+        assert_eq!(
+            DumpFormat::Export
+                .format(&secrets())
+                .expect("format failed"),
+            "export CERT='base64://4='\nexport DB_PASSWORD='it'\\''s \"quoted\"\nline'\n"
+        );
+        assert_eq!(
+            DumpFormat::Yaml.format(&secrets()).expect("format failed"),
+            "CERT: \"base64://4=\"\nDB_PASSWORD: \"it's \\\"quoted\\\"\\nline\"\n"
+        );
+        assert_eq!(
+            DumpFormat::Yaml
+                .format(&BTreeMap::new())
+                .expect("format failed"),
+            "{}\n"
+        );
+
+        let json: BTreeMap<String, String> =
+            serde_json::from_str(&DumpFormat::Json.format(&secrets()).expect("format failed"))
+                .expect("invalid JSON");
+        assert_eq!(json["CERT"], "base64://4=");
+        assert_eq!(json["DB_PASSWORD"], "it's \"quoted\"\nline");
+    }
+}
@@ -11,13 +11,18 @@ use aws_sdk_cloudformation::Error as cloudformationError;
 use aws_sdk_kms::operation::decrypt::DecryptError;
 use aws_sdk_kms::operation::encrypt::EncryptError;
 use aws_sdk_kms::operation::generate_data_key::GenerateDataKeyError;
+use aws_sdk_kms::operation::re_encrypt::ReEncryptError;
 use aws_sdk_s3::error::BuildError;
+use aws_sdk_s3::operation::complete_multipart_upload::CompleteMultipartUploadError;
+use aws_sdk_s3::operation::copy_object::CopyObjectError;
+use aws_sdk_s3::operation::create_multipart_upload::CreateMultipartUploadError;
 use aws_sdk_s3::operation::delete_object::DeleteObjectError;
 use aws_sdk_s3::operation::delete_objects::DeleteObjectsError;
 use aws_sdk_s3::operation::get_object::GetObjectError;
 use aws_sdk_s3::operation::head_object::HeadObjectError;
 use aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Error;
 use aws_sdk_s3::operation::put_object::PutObjectError;
+use aws_sdk_s3::operation::upload_part::UploadPartError;
 use aws_sdk_sts::operation::get_caller_identity::GetCallerIdentityError;
 
 use thiserror::Error;
@@ -25,7 +30,7 @@ use thiserror::Error;
 #[derive(Debug, Error)]
 pub enum VaultError {
     #[error("Describe CloudFormation Stack failed")]
-    DescribeStackError(#[from] SdkError<DescribeStacksError>),
+    DescribeStackError(#[source] Box<SdkError<DescribeStacksError>>),
     #[error("CloudFormation Stack outputs missing")]
     StackOutputsMissingError,
     #[error("Failed to get bucket name from stack")]
@@ -33,11 +38,13 @@ pub enum VaultError {
     #[error("No KEY_ARN provided, can't encrypt")]
     KeyArnMissingError,
     #[error("Failed to generate KMS Data key")]
-    KmsGenerateDataKeyError(#[from] SdkError<GenerateDataKeyError>),
+    KmsGenerateDataKeyError(#[source] Box<SdkError<GenerateDataKeyError>>),
     #[error("Failed to decrypt Ciphertext with KMS")]
-    KmsDecryptError(#[from] SdkError<DecryptError>),
+    KmsDecryptError(#[source] Box<SdkError<DecryptError>>),
     #[error("Failed to encrypt data with KMS")]
-    KmsEncryptError(#[from] SdkError<EncryptError>),
+    KmsEncryptError(#[source] Box<SdkError<EncryptError>>),
+    #[error("Failed to re-encrypt data with KMS")]
+    KmsReEncryptError(#[source] Box<SdkError<ReEncryptError>>),
     #[error("No Plaintext for generated data key")]
     KmsDataKeyPlainTextMissingError,
     #[error("No ciphertextBlob for generated data key")]
@@ -53,9 +60,9 @@ pub enum VaultError {
     #[error("Failed to parse meta with serde")]
     EncryptObjectMetaToJsonError(#[from] serde_json::Error),
     #[error("Failed getting object from S3")]
-    S3GetObjectError(#[from] SdkError<GetObjectError>),
+    S3GetObjectError(#[source] Box<SdkError<GetObjectError>>),
     #[error("Failed deleting object from S3")]
-    S3DeleteObjectError(#[from] SdkError<DeleteObjectError>),
+    S3DeleteObjectError(#[source] Box<SdkError<DeleteObjectError>>),
     #[error("Key does not exist in S3: '{name}'")]
     S3DeleteObjectKeyMissingError { name: String },
     #[error("Failed getting head-object from S3")]
@@ -63,13 +70,29 @@ pub enum VaultError {
     #[error("Failed to decrypt S3-object body")]
     S3GetObjectBodyError,
     #[error("Failed putting object to S3")]
-    S3PutObjectError(#[from] SdkError<PutObjectError>),
+    S3PutObjectError(#[source] Box<SdkError<PutObjectError>>),
     #[error("Failed to list S3 objects")]
-    S3ListObjectsError(#[from] SdkError<ListObjectsV2Error>),
+    S3ListObjectsError(#[source] Box<SdkError<ListObjectsV2Error>>),
     #[error("Failed to build S3 object")]
     S3BuildObjectError(#[from] BuildError),
     #[error("Failed to delete S3 objects")]
-    S3DeleteObjectsError(#[from] SdkError<DeleteObjectsError>),
+    S3DeleteObjectsError(#[source] Box<SdkError<DeleteObjectsError>>),
+    #[error("Failed to delete object '{0}': {1}")]
+    S3DeleteObjectFailedError(String, String),
+    #[error("Failed to delete keys: {}", .0.join(", "))]
+    DeleteManyError(Vec<String>),
+    #[error("Failed to start S3 multipart upload")]
+    S3CreateMultipartUploadError(#[source] Box<SdkError<CreateMultipartUploadError>>),
+    #[error("No upload ID for S3 multipart upload")]
+    S3MultipartUploadIdMissingError,
+    #[error("Too many parts for S3 multipart upload")]
+    S3UploadPartNumberError,
+    #[error("Failed to upload part for S3 multipart upload")]
+    S3UploadPartError(#[source] Box<SdkError<UploadPartError>>),
+    #[error("Failed to complete S3 multipart upload")]
+    S3CompleteMultipartUploadError(#[source] Box<SdkError<CompleteMultipartUploadError>>),
+    #[error("Failed to copy S3 object")]
+    S3CopyObjectError(#[source] Box<SdkError<CopyObjectError>>),
     #[error("No contents found from S3")]
     S3NoContentsError,
     #[error("Failed getting region")]
@@ -80,28 +103,107 @@ pub enum VaultError {
     FileReadError(String, #[source] io::Error),
     #[error("Failed to read from stdin")]
     StdinReadError(#[from] io::Error),
+    #[error("Failed to read data to encrypt")]
+    StreamReadError(#[source] io::Error),
+    #[error("Failed to write decrypted data")]
+    StreamWriteError(#[source] io::Error),
+    #[error("Unsupported encryption algorithm '{0}', a newer vault version may be required")]
+    UnsupportedAlgorithmError(String),
+    #[error("Invalid segment size for streaming encryption: {0:?}")]
+    InvalidSegmentSizeError(Option<usize>),
+    #[error("Failed to compress data")]
+    CompressionError(#[source] io::Error),
+    #[error("Failed to decompress data")]
+    DecompressionError(#[source] io::Error),
     #[error("Deployed stack version not found in the stack data")]
     StackVersionNotFoundError,
     #[error("CloudFormation error: {0}")]
     CloudFormationError(#[from] Box<cloudformationError>),
     #[error("CloudFormation stack update failed: {0}")]
-    UpdateStackError(#[from] SdkError<UpdateStackError>),
+    UpdateStackError(#[source] Box<SdkError<UpdateStackError>>),
     #[error("Account ID missing from caller ID")]
     MissingAccountIdError,
     #[error("Failed to get called ID: {0}")]
-    CallerIdError(#[from] SdkError<GetCallerIdentityError>),
+    CallerIdError(#[source] Box<SdkError<GetCallerIdentityError>>),
     #[error("Failed to create stack: {0}")]
-    CreateStackError(#[from] SdkError<CreateStackError>),
+    CreateStackError(#[source] Box<SdkError<CreateStackError>>),
     #[error("Failed to get stack ID for new vault stack")]
     MissingStackIdError,
     #[error("Failed to get stack status for vault stack")]
     MissingStackStatusError,
-    #[error("Deprecated encryption method for secret. Secret needs to be re-encrypted!")]
+    #[error(
+        "Deprecated encryption method for secret. Secret needs to be re-encrypted with recrypt!"
+    )]
     DeprecatedEncryptionError,
     #[error("Key does not exist in S3")]
     KeyDoesNotExistError,
     #[error("Failed to list stacks: {0}")]
-    ListVaultStacksError(#[from] SdkError<ListStacksError>),
+    ListVaultStacksError(#[source] Box<SdkError<ListStacksError>>),
     #[error("Failed to delete stack: {0}")]
-    DeleteVaultStackError(#[from] SdkError<DeleteStackError>),
+    DeleteVaultStackError(#[source] Box<SdkError<DeleteStackError>>),
+    #[error("Local storage operation failed for '{0}'")]
+    LocalStorageError(String, #[source] io::Error),
+    #[error("Invalid object key for local storage: '{0}'")]
+    InvalidObjectKeyError(String),
+    #[error("Invalid local master key, expected 32 bytes as raw data or base64: '{0}'")]
+    InvalidLocalKeyError(String),
+    #[error("Failed to decrypt data with the local master key")]
+    LocalKeyDecryptError,
+    #[error("Invalid glob pattern: {0}")]
+    GlobPatternError(#[from] globset::Error),
+    #[error("Invalid regex: {0}")]
+    RegexPatternError(#[from] regex::Error),
+    #[error("Invalid or unsupported vault archive")]
+    InvalidArchiveError,
+    #[error("Source and destination are the same key: '{0}'")]
+    SameKeyError(String),
+    #[error("Duration is too long: {0:?}")]
+    DurationOverflowError(std::time::Duration),
+    #[error("Key was modified concurrently: '{0}'")]
+    ConcurrentModificationError(String),
+    #[error("Value is not valid JSON: {0}")]
+    InvalidJsonError(String),
+    #[error("JSON field not found: '{0}'")]
+    JsonFieldNotFoundError(String),
+    #[error("Invalid template: {0}")]
+    TemplateError(String),
+    #[error("Template references missing keys: {}", .0.join(", "))]
+    MissingTemplateKeysError(Vec<String>),
 }
+
+/// Convert AWS SDK errors to the matching variant.
+///
+/// SDK errors are boxed to keep the size of `VaultError` small.
+macro_rules! from_sdk_errors {
+    ($($variant:ident($error:ty)),* $(,)?) => {
+        $(
+            impl From<SdkError<$error>> for VaultError {
+                fn from(error: SdkError<$error>) -> Self {
+                    Self::$variant(Box::new(error))
+                }
+            }
+        )*
+    };
+}
+
+from_sdk_errors!(
+    DescribeStackError(DescribeStacksError),
+    KmsGenerateDataKeyError(GenerateDataKeyError),
+    KmsDecryptError(DecryptError),
+    KmsEncryptError(EncryptError),
+    KmsReEncryptError(ReEncryptError),
+    S3GetObjectError(GetObjectError),
+    S3DeleteObjectError(DeleteObjectError),
+    S3PutObjectError(PutObjectError),
+    S3ListObjectsError(ListObjectsV2Error),
+    S3DeleteObjectsError(DeleteObjectsError),
+    S3CreateMultipartUploadError(CreateMultipartUploadError),
+    S3UploadPartError(UploadPartError),
+    S3CompleteMultipartUploadError(CompleteMultipartUploadError),
+    S3CopyObjectError(CopyObjectError),
+    UpdateStackError(UpdateStackError),
+    CallerIdError(GetCallerIdentityError),
+    CreateStackError(CreateStackError),
+    ListVaultStacksError(ListStacksError),
+    DeleteVaultStackError(DeleteStackError),
+);
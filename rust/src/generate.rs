@@ -0,0 +1,139 @@
+use std::sync::LazyLock;
+
+use rand::Rng;
+
+use crate::Value;
+
+const ALNUM: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+const HEX: &[u8] = b"0123456789abcdef";
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+// Quotes, backslash and backtick are left out so values are easier to use in shell and config files
+const SYMBOLS: &[u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789!#$%&()*+,-./:;<=>?@[]^_{|}~";
+
+static WORDS: LazyLock<Vec<&'static str>> =
+    LazyLock::new(|| include_str!("words.txt").lines().collect());
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+/// Character set for generated secrets.
+///
+/// Characters and words are chosen uniformly at random using the thread-local CSPRNG.
+pub enum Charset {
+    /// ASCII letters and digits.
+    #[default]
+    Alnum,
+    /// Lowercase hexadecimal digits.
+    Hex,
+    /// Characters of the standard base64 alphabet, without padding.
+    Base64,
+    /// ASCII letters, digits and punctuation, except quotes, backslash and backtick.
+    Symbols,
+    /// Lowercase English words separated with `-`, the length is the number of words.
+    Words,
+    /// Raw random bytes stored as a binary value, the length is the number of bytes.
+    Bytes,
+}
+
+impl Charset {
+    #[must_use]
+    /// Generate a random value of the given length.
+    pub fn generate(self, length: usize) -> Value {
+        let mut rng = rand::thread_rng();
+        match self {
+            Self::Alnum => Value::Utf8(random_string(&mut rng, ALNUM, length)),
+            Self::Hex => Value::Utf8(random_string(&mut rng, HEX, length)),
+            Self::Base64 => Value::Utf8(random_string(&mut rng, BASE64, length)),
+            Self::Symbols => Value::Utf8(random_string(&mut rng, SYMBOLS, length)),
+            Self::Words => Value::Utf8(
+                (0..length)
+                    .map(|_| WORDS[rng.gen_range(0..WORDS.len())])
+                    .collect::<Vec<_>>()
+                    .join("-"),
+            ),
+            Self::Bytes => {
+                let mut bytes = vec![0; length];
+                rng.fill(&mut bytes[..]);
+                Value::Binary(bytes)
+            }
+        }
+    }
+
+    #[must_use]
+    /// Number of bits of entropy in a value of the given length.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn entropy_bits(self, length: usize) -> f64 {
+        let choices = match self {
+            Self::Alnum => ALNUM.len(),
+            Self::Hex => HEX.len(),
+            Self::Base64 => BASE64.len(),
+            Self::Symbols => SYMBOLS.len(),
+            Self::Words => WORDS.len(),
+            Self::Bytes => 256,
+        };
+        (choices as f64).log2() * length as f64
+    }
+}
+
+/// Build a string of characters chosen uniformly from the alphabet.
+fn random_string(rng: &mut impl Rng, alphabet: &[u8], length: usize) -> String {
+    (0..length)
+        .map(|_| char::from(alphabet[rng.gen_range(0..alphabet.len())]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Charset, ALNUM, SYMBOLS, WORDS};
+    use crate::Value;
+
+    #[test]
+    fn generate_lengths_and_characters() {
+        let Value::Utf8(alnum) = Charset::Alnum.generate(32) else {
+            panic!("expected UTF-8 value");
+        };
+        assert_eq!(alnum.len(), 32);
+        assert!(alnum.bytes().all(|c| ALNUM.contains(&c)));
+
+        let Value::Utf8(hex) = Charset::Hex.generate(64) else {
+            panic!("expected UTF-8 value");
+        };
+        assert_eq!(hex.len(), 64);
+        assert!(hex.bytes().all(|c| c.is_ascii_hexdigit()));
+
+        let Value::Utf8(symbols) = Charset::Symbols.generate(100) else {
+            panic!("expected UTF-8 value");
+        };
+        assert!(symbols.bytes().all(|c| SYMBOLS.contains(&c)));
+        assert!(!symbols.contains(['"', '\'', '\\', '`']));
+
+        let Value::Utf8(words) = Charset::Words.generate(6) else {
+            panic!("expected UTF-8 value");
+        };
+        assert_eq!(words.split('-').count(), 6);
+        assert!(words.split('-').all(|word| WORDS.contains(&word)));
+
+        assert!(matches!(Charset::Bytes.generate(32), Value::Binary(bytes) if bytes.len() == 32));
+        assert_ne!(
+            Charset::Bytes.generate(32).as_bytes(),
+            Charset::Bytes.generate(32).as_bytes()
+        );
+    }
+
+    #[test]
+    fn word_list_is_unique() {
+        let mut words = WORDS.clone();
+        words.sort_unstable();
+        words.dedup();
+        assert_eq!(words.len(), WORDS.len());
+        assert!(WORDS
+            .iter()
+            .all(|word| word.bytes().all(|c| c.is_ascii_lowercase())));
+    }
+
+    #[test]
+    fn entropy() {
+        assert!((Charset::Bytes.entropy_bits(32) - 256.0).abs() < f64::EPSILON);
+        assert!((Charset::Hex.entropy_bits(64) - 256.0).abs() < f64::EPSILON);
+        assert!(Charset::Alnum.entropy_bits(32) > 190.0);
+    }
+}
@@ -0,0 +1,728 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use aes_gcm::aead::consts::U12;
+use aes_gcm::aead::Aead;
+use aes_gcm::aes::Aes256;
+use aes_gcm::{AesGcm, KeyInit, Nonce};
+use async_trait::async_trait;
+use aws_sdk_kms::primitives::Blob;
+use aws_sdk_kms::types::DataKeySpec;
+use aws_sdk_kms::Client as KmsClient;
+use base64::Engine;
+use rand::Rng;
+use tokio::sync::Mutex;
+
+use crate::errors::VaultError;
+
+/// Length of an AES-256 key in bytes.
+const AES256_KEY_LENGTH: usize = 32;
+/// Length of the AES-GCM nonce in bytes.
+const NONCE_LENGTH: usize = 12;
+
+#[derive(Clone)]
+/// Data key generated for encrypting a single value.
+pub struct DataKey {
+    /// Plaintext key used for encryption. Never stored.
+    pub plaintext: Vec<u8>,
+    /// Encrypted key stored next to the encrypted value.
+    pub ciphertext: Vec<u8>,
+}
+
+#[async_trait]
+/// Key management used by the vault for envelope encryption.
+pub trait KeyProvider: fmt::Debug + Send + Sync {
+    /// Generate a new AES-256 data key.
+    async fn generate_data_key(&self) -> Result<DataKey, VaultError>;
+
+    /// Get a data key for encrypting a value of the given length.
+    ///
+    /// Providers that cache data keys use the length to limit how much data a key encrypts.
+    async fn generate_data_key_for(&self, _plaintext_length: usize) -> Result<DataKey, VaultError> {
+        self.generate_data_key().await
+    }
+
+    /// Decrypt a data key that was stored next to an encrypted value.
+    async fn decrypt_data_key(&self, ciphertext: &[u8]) -> Result<Vec<u8>, VaultError> {
+        self.decrypt(ciphertext).await
+    }
+
+    /// Encrypt data directly with the master key.
+    async fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, VaultError>;
+
+    /// Decrypt data that was encrypted with the master key.
+    async fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, VaultError>;
+
+    /// Identifier for the master key if it can be compared between providers.
+    ///
+    /// Data encrypted by providers with the same key ID can be decrypted by either one.
+    fn key_id(&self) -> Option<&str> {
+        None
+    }
+
+    /// Re-encrypt data from the master key under the given destination key,
+    /// without exposing the plaintext where the provider supports it.
+    ///
+    /// Returns `None` if the data is already encrypted under the destination key.
+    async fn reencrypt(
+        &self,
+        data: &[u8],
+        destination_key: &str,
+    ) -> Result<Option<Vec<u8>>, VaultError>;
+}
+
+#[derive(Debug, Clone)]
+/// Key provider using an AWS KMS key.
+pub struct KmsKeyProvider {
+    client: KmsClient,
+    key_arn: Option<String>,
+}
+
+#[derive(Clone)]
+/// Key provider using a local AES-256 master key.
+///
+/// Data keys are wrapped with AES-GCM and stored as the random nonce followed by the ciphertext.
+pub struct LocalKeyProvider {
+    key: [u8; AES256_KEY_LENGTH],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Limits for reusing cached data keys.
+///
+/// A cached key is used until any of the limits is reached,
+/// after which a new data key is requested from the wrapped provider.
+pub struct DataKeyCacheLimits {
+    /// Maximum time a data key is kept in the cache.
+    pub max_age: Duration,
+    /// Maximum number of values encrypted or decrypted with one cached data key.
+    pub max_uses: u64,
+    /// Maximum number of plaintext bytes encrypted with one cached data key.
+    pub max_bytes: u64,
+}
+
+/// Key provider that caches data keys of another provider.
+///
+/// Generated data keys are reused for encrypting multiple values,
+/// and decrypted data keys are kept so each key is decrypted only once,
+/// which cuts the number of KMS calls in bulk operations.
+/// AES-GCM nonces are random for each value, so reusing a data key is safe within the limits.
+/// Direct encryption and decryption with the master key are not cached.
+pub struct CachingKeyProvider {
+    inner: Box<dyn KeyProvider>,
+    limits: DataKeyCacheLimits,
+    encryption_key: Mutex<Option<CachedDataKey>>,
+    decrypted_keys: Mutex<HashMap<Vec<u8>, CachedDataKey>>,
+}
+
+/// Data key with usage counters.
+struct CachedDataKey {
+    data_key: DataKey,
+    created: Instant,
+    uses: u64,
+    bytes: u64,
+}
+
+impl Default for DataKeyCacheLimits {
+    fn default() -> Self {
+        Self {
+            max_age: Duration::from_secs(5 * 60),
+            max_uses: 1000,
+            max_bytes: 1 << 30,
+        }
+    }
+}
+
+impl fmt::Debug for DataKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DataKey")
+            .field("plaintext", &"<redacted>")
+            .field("ciphertext", &self.ciphertext)
+            .finish()
+    }
+}
+
+impl KmsKeyProvider {
+    #[must_use]
+    /// Create provider for the given KMS key.
+    ///
+    /// The key ARN is only needed for encryption,
+    /// since KMS ciphertext contains the key information for decrypting.
+    pub const fn new(client: KmsClient, key_arn: Option<String>) -> Self {
+        Self { client, key_arn }
+    }
+
+    fn key_arn(&self) -> Result<&str, VaultError> {
+        self.key_arn
+            .as_deref()
+            .ok_or(VaultError::KeyArnMissingError)
+    }
+}
+
+#[async_trait]
+impl KeyProvider for KmsKeyProvider {
+    async fn generate_data_key(&self) -> Result<DataKey, VaultError> {
+        let key_dict = self
+            .client
+            .generate_data_key()
+            .key_id(self.key_arn()?)
+            .key_spec(DataKeySpec::Aes256)
+            .send()
+            .await?;
+
+        let plaintext = key_dict
+            .plaintext()
+            .ok_or(VaultError::KmsDataKeyPlainTextMissingError)?
+            .as_ref()
+            .to_vec();
+
+        let ciphertext = key_dict
+            .ciphertext_blob()
+            .ok_or(VaultError::KmsDataKeyCiphertextBlobMissingError)?
+            .as_ref()
+            .to_vec();
+
+        Ok(DataKey {
+            plaintext,
+            ciphertext,
+        })
+    }
+
+    async fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, VaultError> {
+        let response = self
+            .client
+            .encrypt()
+            .key_id(self.key_arn()?)
+            .plaintext(Blob::new(data))
+            .send()
+            .await
+            .map_err(VaultError::from)?;
+
+        let ciphertext = response
+            .ciphertext_blob
+            .ok_or(VaultError::CiphertextEncryptionError)?
+            .into_inner();
+
+        Ok(ciphertext)
+    }
+
+    async fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, VaultError> {
+        self.client
+            .decrypt()
+            .ciphertext_blob(Blob::new(data))
+            .send()
+            .await?
+            .plaintext()
+            .map(|blob| blob.to_owned().into_inner())
+            .ok_or(VaultError::KmsDataKeyPlainTextMissingError)
+    }
+
+    fn key_id(&self) -> Option<&str> {
+        self.key_arn.as_deref()
+    }
+
+    async fn reencrypt(
+        &self,
+        data: &[u8],
+        destination_key: &str,
+    ) -> Result<Option<Vec<u8>>, VaultError> {
+        let response = self
+            .client
+            .re_encrypt()
+            .ciphertext_blob(Blob::new(data))
+            .destination_key_id(destination_key)
+            .send()
+            .await?;
+
+        // Both IDs are returned as full key ARNs regardless of how the destination was given
+        if response.source_key_id.is_some() && response.source_key_id == response.key_id {
+            return Ok(None);
+        }
+
+        response
+            .ciphertext_blob
+            .map(|blob| Some(blob.into_inner()))
+            .ok_or(VaultError::CiphertextEncryptionError)
+    }
+}
+
+impl LocalKeyProvider {
+    #[must_use]
+    /// Create provider from raw master key bytes.
+    pub const fn new(key: [u8; AES256_KEY_LENGTH]) -> Self {
+        Self { key }
+    }
+
+    #[must_use]
+    /// Create provider with a new random master key.
+    pub fn generate() -> Self {
+        Self::new(random_bytes())
+    }
+
+    /// Read master key from file.
+    ///
+    /// The file can contain either the raw 32 key bytes or the key encoded as base64.
+    /// A new key can be created with for example `openssl rand 32 > vault.key`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, VaultError> {
+        let path = path.as_ref();
+        let contents = std::fs::read(path)
+            .map_err(|e| VaultError::FileReadError(path.display().to_string(), e))?;
+
+        let key = if contents.len() == AES256_KEY_LENGTH {
+            contents
+        } else {
+            let text = String::from_utf8_lossy(&contents);
+            base64::engine::general_purpose::STANDARD
+                .decode(text.trim())
+                .map_err(|_| VaultError::InvalidLocalKeyError(path.display().to_string()))?
+        };
+
+        key.try_into()
+            .map(Self::new)
+            .map_err(|_| VaultError::InvalidLocalKeyError(path.display().to_string()))
+    }
+
+    #[must_use]
+    /// Return the master key encoded as base64, for writing to a key file.
+    pub fn to_base64(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(self.key)
+    }
+
+    fn cipher(&self) -> Result<AesGcm<Aes256, U12>, VaultError> {
+        Ok(AesGcm::new_from_slice(&self.key)?)
+    }
+}
+
+impl fmt::Debug for LocalKeyProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalKeyProvider")
+            .field("key", &"<redacted>")
+            .finish()
+    }
+}
+
+#[async_trait]
+impl KeyProvider for LocalKeyProvider {
+    async fn generate_data_key(&self) -> Result<DataKey, VaultError> {
+        let plaintext = random_bytes::<AES256_KEY_LENGTH>().to_vec();
+        let ciphertext = self.encrypt(&plaintext).await?;
+        Ok(DataKey {
+            plaintext,
+            ciphertext,
+        })
+    }
+
+    async fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, VaultError> {
+        let nonce = Nonce::from(random_bytes::<NONCE_LENGTH>());
+        let ciphertext = self
+            .cipher()?
+            .encrypt(&nonce, data)
+            .map_err(|_| VaultError::CiphertextEncryptionError)?;
+
+        let mut output = nonce.to_vec();
+        output.extend(ciphertext);
+        Ok(output)
+    }
+
+    async fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, VaultError> {
+        if data.len() < NONCE_LENGTH {
+            return Err(VaultError::LocalKeyDecryptError);
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LENGTH);
+        self.cipher()?
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| VaultError::LocalKeyDecryptError)
+    }
+
+    /// Re-encrypt data under the master key read from the given key file.
+    async fn reencrypt(
+        &self,
+        data: &[u8],
+        destination_key: &str,
+    ) -> Result<Option<Vec<u8>>, VaultError> {
+        let destination = Self::from_file(destination_key)?;
+        if destination.decrypt(data).await.is_ok() {
+            return Ok(None);
+        }
+
+        let plaintext = self.decrypt(data).await?;
+        destination.encrypt(&plaintext).await.map(Some)
+    }
+}
+
+impl CachingKeyProvider {
+    #[must_use]
+    /// Wrap the given provider with a data key cache.
+    pub fn new(inner: impl KeyProvider + 'static, limits: DataKeyCacheLimits) -> Self {
+        Self::from_boxed(Box::new(inner), limits)
+    }
+
+    #[must_use]
+    pub(crate) fn from_boxed(inner: Box<dyn KeyProvider>, limits: DataKeyCacheLimits) -> Self {
+        Self {
+            inner,
+            limits,
+            encryption_key: Mutex::new(None),
+            decrypted_keys: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[must_use]
+    /// Return the cache limits.
+    pub const fn limits(&self) -> DataKeyCacheLimits {
+        self.limits
+    }
+
+    /// Remove all cached data keys.
+    pub async fn clear(&self) {
+        self.encryption_key.lock().await.take();
+        self.decrypted_keys.lock().await.clear();
+    }
+}
+
+impl CachedDataKey {
+    fn new(data_key: DataKey, bytes: u64) -> Self {
+        Self {
+            data_key,
+            created: Instant::now(),
+            uses: 1,
+            bytes,
+        }
+    }
+
+    /// Check if the key can still be used for the given number of bytes.
+    fn is_usable(&self, limits: &DataKeyCacheLimits, bytes: u64) -> bool {
+        self.created.elapsed() < limits.max_age
+            && self.uses < limits.max_uses
+            && self.bytes.saturating_add(bytes) <= limits.max_bytes
+    }
+}
+
+impl fmt::Debug for CachingKeyProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CachingKeyProvider")
+            .field("inner", &self.inner)
+            .field("limits", &self.limits)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl KeyProvider for CachingKeyProvider {
+    async fn generate_data_key(&self) -> Result<DataKey, VaultError> {
+        self.generate_data_key_for(0).await
+    }
+
+    async fn generate_data_key_for(&self, plaintext_length: usize) -> Result<DataKey, VaultError> {
+        let bytes = plaintext_length as u64;
+        if bytes > self.limits.max_bytes {
+            return self.inner.generate_data_key_for(plaintext_length).await;
+        }
+
+        // Keep the lock while generating so concurrent stores share one new key
+        let mut cached = self.encryption_key.lock().await;
+        if let Some(entry) = cached.as_mut() {
+            if entry.is_usable(&self.limits, bytes) {
+                entry.uses += 1;
+                entry.bytes += bytes;
+                return Ok(entry.data_key.clone());
+            }
+        }
+
+        let data_key = self.inner.generate_data_key_for(plaintext_length).await?;
+        self.decrypted_keys.lock().await.insert(
+            data_key.ciphertext.clone(),
+            CachedDataKey::new(data_key.clone(), 0),
+        );
+        *cached = Some(CachedDataKey::new(data_key.clone(), bytes));
+        drop(cached);
+        Ok(data_key)
+    }
+
+    async fn decrypt_data_key(&self, ciphertext: &[u8]) -> Result<Vec<u8>, VaultError> {
+        if let Some(entry) = self.decrypted_keys.lock().await.get_mut(ciphertext) {
+            if entry.is_usable(&self.limits, 0) {
+                entry.uses += 1;
+                return Ok(entry.data_key.plaintext.clone());
+            }
+        }
+
+        let plaintext = self.inner.decrypt_data_key(ciphertext).await?;
+        let mut decrypted_keys = self.decrypted_keys.lock().await;
+        decrypted_keys.retain(|_, entry| entry.is_usable(&self.limits, 0));
+        decrypted_keys.insert(
+            ciphertext.to_vec(),
+            CachedDataKey::new(
+                DataKey {
+                    plaintext: plaintext.clone(),
+                    ciphertext: ciphertext.to_vec(),
+                },
+                0,
+            ),
+        );
+        drop(decrypted_keys);
+        Ok(plaintext)
+    }
+
+    async fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, VaultError> {
+        self.inner.encrypt(data).await
+    }
+
+    async fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, VaultError> {
+        self.inner.decrypt(data).await
+    }
+
+    fn key_id(&self) -> Option<&str> {
+        self.inner.key_id()
+    }
+
+    async fn reencrypt(
+        &self,
+        data: &[u8],
+        destination_key: &str,
+    ) -> Result<Option<Vec<u8>>, VaultError> {
+        self.inner.reencrypt(data, destination_key).await
+    }
+}
+
+#[inline]
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    rand::thread_rng().fill(&mut bytes[..]);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+
+    use super::{CachingKeyProvider, DataKey, DataKeyCacheLimits, KeyProvider, LocalKeyProvider};
+    use crate::errors::VaultError;
+
+    /// Local provider that counts calls to the master key.
+    #[derive(Debug, Clone)]
+    struct CountingProvider {
+        inner: LocalKeyProvider,
+        generated: Arc<AtomicU64>,
+        decrypted: Arc<AtomicU64>,
+    }
+
+    impl CountingProvider {
+        fn new() -> Self {
+            Self {
+                inner: LocalKeyProvider::generate(),
+                generated: Arc::new(AtomicU64::new(0)),
+                decrypted: Arc::new(AtomicU64::new(0)),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl KeyProvider for CountingProvider {
+        async fn generate_data_key(&self) -> Result<DataKey, VaultError> {
+            self.generated.fetch_add(1, Ordering::SeqCst);
+            self.inner.generate_data_key().await
+        }
+
+        async fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, VaultError> {
+            self.inner.encrypt(data).await
+        }
+
+        async fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, VaultError> {
+            self.decrypted.fetch_add(1, Ordering::SeqCst);
+            self.inner.decrypt(data).await
+        }
+
+        async fn reencrypt(
+            &self,
+            data: &[u8],
+            destination_key: &str,
+        ) -> Result<Option<Vec<u8>>, VaultError> {
+            self.inner.reencrypt(data, destination_key).await
+        }
+    }
+
+    #[tokio::test]
+    async fn cache_reuses_data_keys_within_limits() {
+        let counter = CountingProvider::new();
+        let cache = CachingKeyProvider::new(
+            counter.clone(),
+            DataKeyCacheLimits {
+                max_uses: 3,
+                max_bytes: 100,
+                ..DataKeyCacheLimits::default()
+            },
+        );
+
+        let first = cache
+            .generate_data_key_for(10)
+            .await
+            .expect("generate failed");
+        for _ in 0..2 {
+            let key = cache
+                .generate_data_key_for(10)
+                .await
+                .expect("generate failed");
+            assert_eq!(key.ciphertext, first.ciphertext);
+        }
+        // Use limit reached
+        let second = cache
+            .generate_data_key_for(10)
+            .await
+            .expect("generate failed");
+        assert_ne!(second.ciphertext, first.ciphertext);
+        // Byte limit reached
+        let third = cache
+            .generate_data_key_for(95)
+            .await
+            .expect("generate failed");
+        assert_ne!(third.ciphertext, second.ciphertext);
+        // Larger than the byte limit, not cached
+        let large = cache
+            .generate_data_key_for(200)
+            .await
+            .expect("generate failed");
+        assert_ne!(large.ciphertext, third.ciphertext);
+        assert_eq!(counter.generated.load(Ordering::SeqCst), 4);
+
+        // Generated keys are decrypted from the cache
+        let plaintext = cache
+            .decrypt_data_key(&third.ciphertext)
+            .await
+            .expect("decrypt failed");
+        assert_eq!(plaintext, third.plaintext);
+        assert_eq!(counter.decrypted.load(Ordering::SeqCst), 0);
+
+        for _ in 0..3 {
+            let plaintext = cache
+                .decrypt_data_key(&large.ciphertext)
+                .await
+                .expect("decrypt failed");
+            assert_eq!(plaintext, large.plaintext);
+        }
+        assert_eq!(counter.decrypted.load(Ordering::SeqCst), 1);
+
+        // Direct decryption is never cached
+        let encrypted = cache.encrypt(b"secret").await.expect("encrypt failed");
+        cache.decrypt(&encrypted).await.expect("decrypt failed");
+        cache.decrypt(&encrypted).await.expect("decrypt failed");
+        assert_eq!(counter.decrypted.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn cache_expires_data_keys() {
+        let counter = CountingProvider::new();
+        let cache = CachingKeyProvider::new(
+            counter.clone(),
+            DataKeyCacheLimits {
+                max_age: Duration::ZERO,
+                ..DataKeyCacheLimits::default()
+            },
+        );
+
+        let first = cache.generate_data_key().await.expect("generate failed");
+        let second = cache.generate_data_key().await.expect("generate failed");
+        assert_ne!(first.ciphertext, second.ciphertext);
+
+        cache
+            .decrypt_data_key(&first.ciphertext)
+            .await
+            .expect("decrypt failed");
+        assert_eq!(counter.decrypted.load(Ordering::SeqCst), 1);
+
+        cache.clear().await;
+        cache
+            .decrypt_data_key(&second.ciphertext)
+            .await
+            .expect("decrypt failed");
+        assert_eq!(counter.decrypted.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn local_encrypt_and_decrypt() {
+        let provider = LocalKeyProvider::generate();
+        let encrypted = provider.encrypt(b"secret").await.expect("encrypt failed");
+        assert_ne!(encrypted, b"secret");
+
+        let decrypted = provider.decrypt(&encrypted).await.expect("decrypt failed");
+        assert_eq!(decrypted, b"secret");
+    }
+
+    #[tokio::test]
+    async fn local_data_key_roundtrip() {
+        let provider = LocalKeyProvider::generate();
+        let data_key = provider.generate_data_key().await.expect("generate failed");
+        assert_eq!(data_key.plaintext.len(), 32);
+
+        let decrypted = provider
+            .decrypt(&data_key.ciphertext)
+            .await
+            .expect("decrypt failed");
+        assert_eq!(decrypted, data_key.plaintext);
+    }
+
+    #[tokio::test]
+    async fn local_decrypt_with_wrong_key() {
+        let encrypted = LocalKeyProvider::generate()
+            .encrypt(b"secret")
+            .await
+            .expect("encrypt failed");
+
+        let result = LocalKeyProvider::generate().decrypt(&encrypted).await;
+        assert!(matches!(result, Err(VaultError::LocalKeyDecryptError)));
+    }
+
+    #[tokio::test]
+    async fn local_reencrypt() {
+        let source = LocalKeyProvider::generate();
+        let destination = LocalKeyProvider::generate();
+        let path = std::env::temp_dir().join(format!("nitor-vault-key-{}", rand::random::<u64>()));
+        std::fs::write(&path, destination.to_base64()).expect("write failed");
+        let path = path.display().to_string();
+
+        let encrypted = source.encrypt(b"secret").await.expect("encrypt failed");
+        let reencrypted = source
+            .reencrypt(&encrypted, &path)
+            .await
+            .expect("reencrypt failed")
+            .expect("expected new ciphertext");
+
+        let decrypted = destination
+            .decrypt(&reencrypted)
+            .await
+            .expect("decrypt failed");
+        assert_eq!(decrypted, b"secret");
+
+        // Already encrypted under the destination key
+        let result = source
+            .reencrypt(&reencrypted, &path)
+            .await
+            .expect("reencrypt failed");
+        assert!(result.is_none());
+
+        std::fs::remove_file(&path).expect("cleanup failed");
+    }
+
+    #[test]
+    fn local_key_from_base64_file() {
+        let provider = LocalKeyProvider::generate();
+        let path = std::env::temp_dir().join(format!("nitor-vault-key-{}", rand::random::<u64>()));
+        std::fs::write(&path, format!("{}\n", provider.to_base64())).expect("write failed");
+
+        let loaded = LocalKeyProvider::from_file(&path).expect("load failed");
+        assert_eq!(loaded.key, provider.key);
+
+        std::fs::write(&path, "too short").expect("write failed");
+        assert!(matches!(
+            LocalKeyProvider::from_file(&path),
+            Err(VaultError::InvalidLocalKeyError(_))
+        ));
+
+        std::fs::remove_file(&path).expect("cleanup failed");
+    }
+}
@@ -1,29 +1,52 @@
+pub mod archive;
 pub mod args;
 pub mod cli;
 pub mod cloudformation;
 pub mod errors;
+pub mod key_provider;
+pub mod storage;
 
+mod compression;
+mod dump;
+mod generate;
+mod matcher;
+mod render;
+mod stream;
 mod template;
+mod tree;
 mod value;
 mod vault;
 
 // Expose `Vault` and `Value` so they can be used as if they were defined here
 pub use crate::args::run_cli;
 pub use crate::args::run_cli_with_args;
+pub use crate::compression::Compression;
+pub use crate::dump::DumpFormat;
+pub use crate::generate::Charset;
+pub use crate::matcher::KeyMatcher;
+pub use crate::render::Template;
+pub use crate::tree::KeyTree;
 pub use crate::value::Value;
 pub use crate::vault::Vault;
 
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::SystemTime;
+
 use aws_config::meta::region::RegionProviderChain;
 use aws_config::{Region, SdkConfig};
-use aws_sdk_s3::types::ObjectIdentifier;
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
 use aws_sdk_sts::config::Credentials;
 use base64::Engine;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 
 use crate::cloudformation::CloudFormationStackData;
 use crate::errors::VaultError;
+use crate::key_provider::DataKeyCacheLimits;
+use crate::storage::ObjectInfo;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
 /// Result data for initializing a new vault stack.
 pub enum CreateStackResult {
     /// Vault stack has already been initialized.
@@ -34,11 +57,13 @@ pub enum CreateStackResult {
     Created {
         stack_name: String,
         stack_id: String,
+        #[serde(serialize_with = "serialize_region")]
         region: Region,
     },
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
 /// Result data for updating the vault stack.
 pub enum UpdateStackResult {
     /// Vault stack is up to date. No update needed.
@@ -51,6 +76,69 @@ pub enum UpdateStackResult {
     },
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+/// Storage format of a secret.
+pub enum SecretFormat {
+    /// Current format with data key, AES-GCM ciphertext and meta objects.
+    AesGcm,
+    /// Old deprecated AES-CTR format that needs to be re-encrypted.
+    Deprecated,
+    /// Some of the objects for the secret are missing.
+    Incomplete,
+}
+
+#[derive(Debug, Clone, Serialize)]
+/// Information about a stored secret from the storage object metadata.
+pub struct SecretInfo {
+    /// Key name without the prefix.
+    pub name: String,
+    /// Size of the encrypted value in bytes.
+    pub size: u64,
+    /// Last modification time of the encrypted value.
+    #[serde(serialize_with = "serialize_timestamp")]
+    pub last_modified: Option<DateTime>,
+    /// S3 entity tag of the encrypted value.
+    pub etag: Option<String>,
+    pub format: SecretFormat,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+/// Immediate children of a key path.
+pub struct DirectoryListing {
+    /// Key names directly under the listed path.
+    pub keys: Vec<String>,
+    /// Nested paths under the listed path with the number of keys in each.
+    pub directories: Vec<DirectoryCount>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+/// Nested path in a directory listing.
+pub struct DirectoryCount {
+    /// Full path ending with `/`.
+    pub path: String,
+    /// Number of keys in the subtree under the path.
+    pub count: usize,
+}
+
+/// Results of a bulk operation for each key name, in the same order as the given names.
+pub type BulkResult<T> = Vec<(String, Result<T, VaultError>)>;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Differences between the secrets of two vaults or paths.
+///
+/// All lists are sorted by key name.
+pub struct VaultDiff {
+    /// Keys that only exist in the compared vault.
+    pub only_self: Vec<String>,
+    /// Keys that only exist in the other vault.
+    pub only_other: Vec<String>,
+    /// Keys that exist in both vaults with different values.
+    pub changed: Vec<String>,
+    /// Keys that exist in both vaults with the same value.
+    pub unchanged: Vec<String>,
+}
+
 #[derive(Debug, Default, Clone)]
 /// Optional parameters for a `Vault` instance.
 pub struct VaultConfig {
@@ -62,6 +150,17 @@ pub struct VaultConfig {
     pub profile: Option<String>,
     pub iam_id: Option<String>,
     pub iam_secret: Option<String>,
+    /// Use a local directory for storage instead of the S3 bucket.
+    pub local_dir: Option<String>,
+    /// Use a local master key file for encryption instead of KMS.
+    pub local_key: Option<String>,
+    /// Cache data keys with the given limits to reduce KMS calls.
+    pub data_key_cache: Option<DataKeyCacheLimits>,
+    /// Don't use the `VAULT_*` environment variables for missing values.
+    ///
+    /// Set this for a vault that is configured separately from the current one,
+    /// so its bucket and key are resolved from its own stack.
+    pub ignore_env: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -71,10 +170,52 @@ pub(crate) struct EncryptObject {
     meta: String,
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+/// User metadata for a secret, stored in the meta object next to the algorithm and nonce.
+///
+/// The meta object is the AES-GCM additional authenticated data,
+/// so the metadata is authenticated when the secret is decrypted.
+/// All fields are optional so secrets stored without metadata can still be read,
+/// and old clients ignore the extra fields.
+pub struct SecretMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+    /// Creation time as an RFC 3339 timestamp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    /// Last update time as an RFC 3339 timestamp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<String>,
+    /// IAM ARN of the identity that created the secret.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    /// Expiry time as an RFC 3339 timestamp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct Meta {
     alg: String,
     nonce: String,
+    /// Plaintext segment size for values encrypted in the streaming format.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    segment_size: Option<usize>,
+    #[serde(flatten)]
+    metadata: SecretMetadata,
+}
+
+#[derive(Debug, Default)]
+/// Storage objects found for a single value when listing.
+pub(crate) struct SecretObjects {
+    key: Option<ObjectInfo>,
+    cipher: Option<ObjectInfo>,
+    meta: Option<ObjectInfo>,
+    legacy_cipher: Option<ObjectInfo>,
 }
 
 #[derive(Debug, Clone)]
@@ -83,21 +224,45 @@ pub(crate) struct S3DataKeys {
     key: String,
     cipher: String,
     meta: String,
+    /// AES-CTR ciphertext used by the old deprecated encryption method.
+    legacy_cipher: String,
 }
 
 impl Meta {
     #[must_use]
-    fn new(algorithm: &str, nonce: &[u8]) -> Self {
+    fn new(algorithm: &str, nonce: &[u8], metadata: SecretMetadata) -> Self {
         Self {
             alg: algorithm.to_owned(),
             nonce: base64::engine::general_purpose::STANDARD.encode(nonce),
+            segment_size: None,
+            metadata,
         }
     }
 
     #[must_use]
     /// Shorthand to initialize new Meta with AES-GCM algorithm.
-    fn aesgcm(nonce: &[u8]) -> Self {
-        Self::new("AESGCM", nonce)
+    fn aesgcm(nonce: &[u8], metadata: SecretMetadata) -> Self {
+        Self::new("AESGCM", nonce, metadata)
+    }
+
+    #[must_use]
+    /// Shorthand to initialize new Meta with the segmented AES-GCM streaming algorithm.
+    fn aesgcm_stream(nonce: &[u8], segment_size: usize, metadata: SecretMetadata) -> Self {
+        Self {
+            segment_size: Some(segment_size),
+            ..Self::new(stream::STREAM_ALGORITHM, nonce, metadata)
+        }
+    }
+
+    #[must_use]
+    /// Shorthand to initialize new Meta with AES-GCM algorithm for a value compressed with the codec.
+    fn aesgcm_compressed(nonce: &[u8], compression: Compression, metadata: SecretMetadata) -> Self {
+        Self::new(compression.algorithm(), nonce, metadata)
+    }
+
+    /// Codec the value was compressed with before encryption, based on the algorithm.
+    fn compression(&self) -> Option<Compression> {
+        Compression::from_algorithm(&self.alg)
     }
 
     /// Serialize Meta to JSON string.
@@ -106,34 +271,209 @@ impl Meta {
     }
 }
 
+impl VaultConfig {
+    /// Use the `VAULT_*` environment variables for missing values unless `ignore_env` is set.
+    fn with_env_defaults(self) -> Self {
+        if self.ignore_env {
+            return self;
+        }
+        Self {
+            vault_stack: self.vault_stack.or_else(|| get_env_variable("VAULT_STACK")),
+            bucket: self.bucket.or_else(|| get_env_variable("VAULT_BUCKET")),
+            key: self.key.or_else(|| get_env_variable("VAULT_KEY")),
+            prefix: self.prefix.or_else(|| get_env_variable("VAULT_PREFIX")),
+            local_dir: self
+                .local_dir
+                .or_else(|| get_env_variable("VAULT_LOCAL_DIR")),
+            local_key: self
+                .local_key
+                .or_else(|| get_env_variable("VAULT_LOCAL_KEY")),
+            ..self
+        }
+    }
+}
+
+impl SecretMetadata {
+    #[must_use]
+    /// Return true if all the given tags are set with the same values.
+    pub fn has_tags(&self, tags: &BTreeMap<String, String>) -> bool {
+        tags.iter()
+            .all(|(key, value)| self.tags.get(key) == Some(value))
+    }
+
+    #[must_use]
+    /// Return the expiry time if set and valid.
+    pub fn expires_at(&self) -> Option<DateTime> {
+        self.expires
+            .as_deref()
+            .and_then(|expires| DateTime::from_str(expires, DateTimeFormat::DateTime).ok())
+    }
+
+    #[must_use]
+    /// Return the last update time if set and valid.
+    pub fn updated_at(&self) -> Option<DateTime> {
+        self.updated
+            .as_deref()
+            .and_then(|updated| DateTime::from_str(updated, DateTimeFormat::DateTime).ok())
+    }
+
+    #[must_use]
+    /// Set the expiry time.
+    pub fn with_expiry(mut self, expires: DateTime) -> Self {
+        self.expires = format_timestamp(expires);
+        self
+    }
+
+    #[must_use]
+    /// Return true if the expiry time has passed.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at()
+            .is_some_and(|expires| expires <= DateTime::from(SystemTime::now()))
+    }
+
+    /// Update fields that are set in the given metadata.
+    ///
+    /// Tags are merged, with new values replacing existing ones.
+    /// Timestamps and the creator are not updated.
+    fn update(&mut self, metadata: Self) {
+        if metadata.description.is_some() {
+            self.description = metadata.description;
+        }
+        if metadata.owner.is_some() {
+            self.owner = metadata.owner;
+        }
+        if metadata.expires.is_some() {
+            self.expires = metadata.expires;
+        }
+        self.tags.extend(metadata.tags);
+    }
+}
+
+impl fmt::Display for SecretMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tags = self
+            .tags
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(
+            f,
+            "description: {}\nowner: {}\ntags: {}\ncreated: {}\nupdated: {}\ncreated by: {}\nexpires: {}",
+            self.description.as_deref().unwrap_or("None"),
+            self.owner.as_deref().unwrap_or("None"),
+            if tags.is_empty() { "None" } else { &tags },
+            self.created.as_deref().unwrap_or("None"),
+            self.updated.as_deref().unwrap_or("None"),
+            self.created_by.as_deref().unwrap_or("None"),
+            self.expires.as_deref().unwrap_or("None"),
+        )
+    }
+}
+
 impl S3DataKeys {
     fn new(name: &str) -> Self {
         Self {
             key: format!("{name}.key"),
             cipher: format!("{name}.aesgcm.encrypted"),
             meta: format!("{name}.meta"),
+            legacy_cipher: format!("{name}.encrypted"),
         }
     }
 
-    /// Return key strings as an array for easy iteration.
-    fn as_array(&self) -> [&str; 3] {
-        [&self.key, &self.cipher, &self.meta]
+    /// Return owned key strings for all objects.
+    fn to_vec(&self) -> Vec<String> {
+        vec![
+            self.key.clone(),
+            self.cipher.clone(),
+            self.meta.clone(),
+            self.legacy_cipher.clone(),
+        ]
     }
+}
 
-    /// Convert keys to S3 object identifiers.
-    fn to_object_identifiers(&self) -> Result<Vec<ObjectIdentifier>, VaultError> {
-        self.as_array()
-            .iter()
-            .map(|key| {
-                ObjectIdentifier::builder()
-                    .set_key(Some((*key).to_string()))
-                    .build()
-                    .map_err(VaultError::from)
-            })
-            .collect()
+impl SecretObjects {
+    /// Add listed object to the entry for its key name.
+    ///
+    /// Objects that don't belong to a stored value are ignored.
+    fn insert(secrets: &mut BTreeMap<String, Self>, object: ObjectInfo) {
+        // Check AES-GCM suffix first since the legacy suffix is a subset of it
+        let (name, field): (_, fn(&mut Self) -> &mut Option<ObjectInfo>) =
+            if let Some(name) = object.key.strip_suffix(".aesgcm.encrypted") {
+                (name, |objects| &mut objects.cipher)
+            } else if let Some(name) = object.key.strip_suffix(".encrypted") {
+                (name, |objects| &mut objects.legacy_cipher)
+            } else if let Some(name) = object.key.strip_suffix(".key") {
+                (name, |objects| &mut objects.key)
+            } else if let Some(name) = object.key.strip_suffix(".meta") {
+                (name, |objects| &mut objects.meta)
+            } else {
+                return;
+            };
+
+        let entry = secrets.entry(name.to_owned()).or_default();
+        *field(entry) = Some(object);
+    }
+
+    /// Storage format based on which objects exist.
+    const fn format(&self) -> SecretFormat {
+        match (&self.key, &self.cipher, &self.meta, &self.legacy_cipher) {
+            (Some(_), Some(_), Some(_), _) => SecretFormat::AesGcm,
+            (Some(_), None, None, Some(_)) => SecretFormat::Deprecated,
+            _ => SecretFormat::Incomplete,
+        }
+    }
+
+    fn into_info(self, name: String) -> SecretInfo {
+        let format = self.format();
+        let object = self
+            .cipher
+            .or(self.legacy_cipher)
+            .or(self.key)
+            .or(self.meta);
+
+        SecretInfo {
+            name,
+            size: object.as_ref().map_or(0, |object| object.size),
+            last_modified: object.as_ref().and_then(|object| object.last_modified),
+            etag: object.and_then(|object| object.etag),
+            format,
+        }
+    }
+}
+
+impl fmt::Display for SecretFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AesGcm => write!(f, "aesgcm"),
+            Self::Deprecated => write!(f, "deprecated"),
+            Self::Incomplete => write!(f, "incomplete"),
+        }
     }
 }
 
+#[must_use]
+/// Format time as an RFC 3339 timestamp with seconds precision.
+pub(crate) fn format_timestamp(time: DateTime) -> Option<String> {
+    DateTime::from_secs(time.secs())
+        .fmt(DateTimeFormat::DateTime)
+        .ok()
+}
+
+/// Serialize optional time as an RFC 3339 timestamp.
+#[allow(clippy::ref_option)]
+fn serialize_timestamp<S: Serializer>(
+    time: &Option<DateTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    time.and_then(format_timestamp).serialize(serializer)
+}
+
+/// Serialize region as its name.
+fn serialize_region<S: Serializer>(region: &Region, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(region.as_ref())
+}
+
 #[inline]
 #[must_use]
 /// Return possible env variable value as Option.
@@ -0,0 +1,120 @@
+use globset::{GlobBuilder, GlobMatcher};
+use regex::Regex;
+
+use crate::errors::VaultError;
+
+/// Characters with a special meaning in glob patterns.
+const GLOB_CHARACTERS: [char; 5] = ['*', '?', '[', '{', '\\'];
+
+#[derive(Debug, Clone)]
+/// Matches key names with either a glob pattern or a regex.
+///
+/// In glob patterns `*` and `?` do not match the `/` separator,
+/// while `**` matches any number of nested paths.
+pub enum KeyMatcher {
+    Glob {
+        pattern: String,
+        matcher: GlobMatcher,
+    },
+    Regex(Regex),
+}
+
+impl KeyMatcher {
+    /// Create matcher from a glob pattern.
+    pub fn glob(pattern: &str) -> Result<Self, VaultError> {
+        let matcher = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .backslash_escape(true)
+            .build()?
+            .compile_matcher();
+
+        Ok(Self::Glob {
+            pattern: pattern.to_string(),
+            matcher,
+        })
+    }
+
+    /// Create matcher from a regex.
+    ///
+    /// The regex is not anchored, so it matches anywhere in the key name
+    /// unless `^` and `$` are used.
+    pub fn regex(pattern: &str) -> Result<Self, VaultError> {
+        Ok(Self::Regex(Regex::new(pattern)?))
+    }
+
+    #[must_use]
+    /// Return true if the given string contains glob special characters.
+    pub fn is_glob(pattern: &str) -> bool {
+        pattern.contains(GLOB_CHARACTERS)
+    }
+
+    #[must_use]
+    /// Return true if the key name matches.
+    pub fn is_match(&self, name: &str) -> bool {
+        match self {
+            Self::Glob { matcher, .. } => matcher.is_match(name),
+            Self::Regex(regex) => regex.is_match(name),
+        }
+    }
+
+    #[must_use]
+    /// Return the literal path all matching keys start with.
+    ///
+    /// This is the part of a glob pattern before the first special character,
+    /// up to and including the last `/` separator.
+    /// Used for limiting the storage listing, so always empty for regex.
+    pub fn path_prefix(&self) -> &str {
+        match self {
+            Self::Glob { pattern, .. } => {
+                let literal = pattern
+                    .find(GLOB_CHARACTERS)
+                    .map_or(pattern.as_str(), |index| &pattern[..index]);
+                literal.rfind('/').map_or("", |index| &literal[..=index])
+            }
+            Self::Regex(_) => "",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KeyMatcher;
+
+    #[test]
+    fn glob_matches_within_path_segments() {
+        let matcher = KeyMatcher::glob("prod/**/db-*").expect("invalid glob");
+        assert!(matcher.is_match("prod/db-main"));
+        assert!(matcher.is_match("prod/eu/app/db-main"));
+        assert!(!matcher.is_match("prod/eu/app/api"));
+        assert!(!matcher.is_match("dev/db-main"));
+        assert_eq!(matcher.path_prefix(), "prod/");
+
+        let matcher = KeyMatcher::glob("prod/*").expect("invalid glob");
+        assert!(matcher.is_match("prod/key"));
+        assert!(!matcher.is_match("prod/nested/key"));
+    }
+
+    #[test]
+    fn regex_matches_anywhere() {
+        let matcher = KeyMatcher::regex("db-(main|replica)$").expect("invalid regex");
+        assert!(matcher.is_match("prod/db-main"));
+        assert!(matcher.is_match("db-replica"));
+        assert!(!matcher.is_match("prod/db-main/password"));
+        assert_eq!(matcher.path_prefix(), "");
+    }
+
+    #[test]
+    fn path_prefix_without_special_characters() {
+        let matcher = KeyMatcher::glob("prod/app/key").expect("invalid glob");
+        assert_eq!(matcher.path_prefix(), "prod/app/");
+
+        let matcher = KeyMatcher::glob("key*").expect("invalid glob");
+        assert_eq!(matcher.path_prefix(), "");
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        assert!(KeyMatcher::glob("prod/[a").is_err());
+        assert!(KeyMatcher::regex("prod/(a").is_err());
+    }
+}
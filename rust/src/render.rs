@@ -0,0 +1,325 @@
+use std::collections::{BTreeSet, HashMap};
+
+use base64::Engine;
+
+use crate::errors::VaultError;
+use crate::Value;
+
+/// Error message for binary values used without the `base64` filter.
+const BINARY_VALUE_ERROR: &str = "binary value needs the `base64` filter";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Text template with vault placeholders.
+///
+/// Placeholders have the form `{{ vault "key/name" }}`,
+/// optionally followed by filters separated with `|`:
+/// - `base64`: encode the value as base64
+/// - `json`: escape the value for use inside a JSON string
+/// - `trim`: remove leading and trailing whitespace
+/// - `default "value"`: use the given value if the key does not exist
+///
+/// Binary values need the `base64` filter, since they can't be rendered as text.
+/// Other `{{ ... }}` expressions are left as is,
+/// so templates can also be used with other template engines.
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Placeholder { key: String, filters: Vec<Filter> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Filter {
+    Base64,
+    Json,
+    Trim,
+    Default(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Pipe,
+}
+
+impl Template {
+    /// Parse template text.
+    pub fn parse(text: &str) -> Result<Self, VaultError> {
+        let mut parts = Vec::new();
+        let mut rest = text;
+        let mut literal = String::new();
+        while let Some(start) = rest.find("{{") {
+            let Some(length) = rest[start..].find("}}") else {
+                break;
+            };
+            let expression = &rest[start + 2..start + length];
+            literal.push_str(&rest[..start]);
+            match expression.trim_start().strip_prefix("vault") {
+                Some(placeholder) if placeholder.starts_with(char::is_whitespace) => {
+                    let line = text[..text.len() - rest.len() + start]
+                        .matches('\n')
+                        .count()
+                        + 1;
+                    let (key, filters) = parse_placeholder(placeholder).map_err(|message| {
+                        VaultError::TemplateError(format!("line {line}: {message}"))
+                    })?;
+                    if !literal.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Placeholder { key, filters });
+                }
+                _ => literal.push_str(&rest[start..start + length + 2]),
+            }
+            rest = &rest[start + length + 2..];
+        }
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            parts.push(Part::Text(literal));
+        }
+        Ok(Self { parts })
+    }
+
+    #[must_use]
+    /// Return all key names referenced in the template.
+    pub fn keys(&self) -> BTreeSet<&str> {
+        self.parts
+            .iter()
+            .filter_map(|part| match part {
+                Part::Placeholder { key, .. } => Some(key.as_str()),
+                Part::Text(_) => None,
+            })
+            .collect()
+    }
+
+    /// Render template with the given values.
+    ///
+    /// Returns an error listing all keys that are missing from the values and have no default,
+    /// or an error if a binary value is used without the `base64` filter.
+    pub fn render(&self, values: &HashMap<String, Value>) -> Result<String, VaultError> {
+        let mut output = String::new();
+        let mut missing = BTreeSet::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => output.push_str(text),
+                Part::Placeholder { key, filters } => {
+                    match apply_filters(values.get(key).cloned(), filters) {
+                        Ok(Some(value)) => output.push_str(&value.to_string()),
+                        Err(message) => {
+                            return Err(VaultError::TemplateError(format!(
+                                "key '{key}': {message}"
+                            )));
+                        }
+                        Ok(None) => {
+                            missing.insert(key.clone());
+                        }
+                    }
+                }
+            }
+        }
+        if missing.is_empty() {
+            Ok(output)
+        } else {
+            Err(VaultError::MissingTemplateKeysError(
+                missing.into_iter().collect(),
+            ))
+        }
+    }
+}
+
+/// Apply filters in order to the value.
+///
+/// Returns `None` if the key does not exist and there is no default.
+/// Binary values are only accepted as input to the `base64` filter.
+fn apply_filters(mut value: Option<Value>, filters: &[Filter]) -> Result<Option<Value>, String> {
+    for filter in filters {
+        value = match (filter, value) {
+            (Filter::Default(default), None) => Some(Value::Utf8(default.clone())),
+            (_, None) => None,
+            (Filter::Base64, Some(value)) => Some(Value::Utf8(
+                base64::engine::general_purpose::STANDARD.encode(value.as_bytes()),
+            )),
+            (Filter::Json | Filter::Trim, Some(Value::Binary(_))) => {
+                return Err(BINARY_VALUE_ERROR.to_string())
+            }
+            (Filter::Json, Some(value)) => {
+                let quoted = serde_json::Value::String(value.to_string()).to_string();
+                Some(Value::Utf8(quoted[1..quoted.len() - 1].to_string()))
+            }
+            (Filter::Trim, Some(value)) => Some(Value::Utf8(value.to_string().trim().to_string())),
+            (Filter::Default(_), Some(value)) => Some(value),
+        };
+    }
+    match value {
+        Some(Value::Binary(_)) => Err(BINARY_VALUE_ERROR.to_string()),
+        value => Ok(value),
+    }
+}
+
+/// Parse placeholder contents after the `vault` keyword.
+fn parse_placeholder(placeholder: &str) -> Result<(String, Vec<Filter>), String> {
+    let mut tokens = tokenize(placeholder)?.into_iter();
+    let Some(Token::Quoted(key)) = tokens.next() else {
+        return Err("expected quoted key name after 'vault'".to_string());
+    };
+
+    let mut filters = Vec::new();
+    while let Some(token) = tokens.next() {
+        if token != Token::Pipe {
+            return Err("expected '|' before filter".to_string());
+        }
+        let filter = match tokens.next() {
+            Some(Token::Word(name)) => match name.as_str() {
+                "base64" => Filter::Base64,
+                "json" => Filter::Json,
+                "trim" => Filter::Trim,
+                "default" => match tokens.next() {
+                    Some(Token::Quoted(default)) => Filter::Default(default),
+                    _ => return Err("expected quoted value after 'default'".to_string()),
+                },
+                _ => return Err(format!("unknown filter '{name}'")),
+            },
+            _ => return Err("expected filter name after '|'".to_string()),
+        };
+        filters.push(filter);
+    }
+    Ok((key, filters))
+}
+
+/// Split placeholder into words, quoted strings and pipes.
+///
+/// Quoted strings support `\"` and `\\` escapes.
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '|' => tokens.push(Token::Pipe),
+            '"' => {
+                let mut quoted = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped @ ('"' | '\\')) => quoted.push(escaped),
+                            _ => return Err("invalid escape in quoted string".to_string()),
+                        },
+                        Some(c) => quoted.push(c),
+                        None => return Err("unterminated quoted string".to_string()),
+                    }
+                }
+                tokens.push(Token::Quoted(quoted));
+            }
+            c => {
+                let mut word = String::from(c);
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '|' || c == '"' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::Template;
+    use crate::errors::VaultError;
+    use crate::Value;
+
+    fn values() -> HashMap<String, Value> {
+        HashMap::from([
+            (
+                "prod/db/password".to_string(),
+                Value::Utf8(" p\"ss\n".to_string()),
+            ),
+            ("binary".to_string(), Value::Binary(vec![0xff, 0xfe])),
+        ])
+    }
+
+    #[test]
+    fn render_placeholders_and_filters() {
+        let template = Template::parse(
+            "password={{ vault \"prod/db/password\" | trim }}\n\
+             json=\"{{vault \"prod/db/password\" | json}}\"\n\
+             b64={{ vault \"binary\" | base64 }}\n\
+             missing={{ vault \"missing\" | default \"fallback\" | base64 }}\n\
+             helm={{ .Values.name }}",
+        )
+        .expect("parse failed");
+        assert_eq!(
+            template.keys().into_iter().collect::<Vec<_>>(),
+            vec!["binary", "missing", "prod/db/password"]
+        );
+
+        let rendered = template.render(&values()).expect("render failed");
+        assert_eq!(
+            rendered,
+            "password=p\"ss\n\
+             json=\" p\\\"ss\\n\"\n\
+             b64=//4=\n\
+             missing=ZmFsbGJhY2s=\n\
+             helm={{ .Values.name }}"
+        );
+    }
+
+    #[test]
+    fn render_lists_all_missing_keys() {
+        let template =
+            Template::parse("{{ vault \"b\" }} {{ vault \"a\" }} {{ vault \"prod/db/password\" }}")
+                .expect("parse failed");
+        let result = template.render(&values());
+        assert!(
+            matches!(result, Err(VaultError::MissingTemplateKeysError(keys)) if keys == ["a", "b"])
+        );
+    }
+
+    #[test]
+    fn render_binary_without_base64() {
+        for text in [
+            "{{ vault \"binary\" }}",
+            "{{ vault \"binary\" | trim }}",
+            "{{ vault \"binary\" | json | base64 }}",
+        ] {
+            let template = Template::parse(text).expect("parse failed");
+            assert!(
+                matches!(
+                    template.render(&values()),
+                    Err(VaultError::TemplateError(message)) if message.contains("base64")
+                ),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        for text in [
+            "{{ vault prod }}",
+            "{{ vault \"a\" | upper }}",
+            "{{ vault \"a\" | default }}",
+            "{{ vault \"a\" trim }}",
+            "\n{{ vault \"a }}",
+        ] {
+            assert!(
+                matches!(Template::parse(text), Err(VaultError::TemplateError(_))),
+                "{text}"
+            );
+        }
+        assert!(matches!(
+            Template::parse("\n\n{{ vault 'a' }}"),
+            Err(VaultError::TemplateError(message)) if message.starts_with("line 3:")
+        ));
+    }
+}
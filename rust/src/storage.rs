@@ -0,0 +1,876 @@
+use std::fmt;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use async_trait::async_trait;
+use aws_sdk_s3::error::ProvideErrorMetadata;
+use aws_sdk_s3::operation::get_object::GetObjectOutput;
+use aws_sdk_s3::primitives::{ByteStream, DateTime};
+use aws_sdk_s3::types::{
+    CompletedMultipartUpload, CompletedPart, Delete, Object, ObjectCannedAcl, ObjectIdentifier,
+};
+use aws_sdk_s3::Client as S3Client;
+use futures::stream::BoxStream;
+use futures::TryStreamExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::errors::VaultError;
+
+/// Maximum number of objects deleted with a single S3 `DeleteObjects` request.
+pub const MAX_DELETE_OBJECTS: usize = 1000;
+
+/// Minimum size of a part in S3 multipart uploads, except for the last part.
+/// Larger objects are uploaded in parts of at least this size.
+const MULTIPART_PART_SIZE: usize = 8 * 1024 * 1024;
+
+/// Maximum number of parts in S3 multipart uploads.
+const MAX_MULTIPART_PARTS: usize = 10_000;
+
+/// Size of chunks read from local files.
+const LOCAL_READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Object data as a stream of chunks.
+pub type ObjectStream<'a> = BoxStream<'a, Result<Vec<u8>, VaultError>>;
+
+#[async_trait]
+/// Object storage used by the vault for the encrypted data.
+///
+/// Keys are `/`-separated object names like the S3 object keys used by the vault.
+pub trait Storage: fmt::Debug + Send + Sync {
+    /// Write data for the given object key, replacing any existing data.
+    async fn put_object(&self, key: &str, data: Vec<u8>) -> Result<(), VaultError>;
+
+    /// Read data for the given object key.
+    ///
+    /// Returns `VaultError::KeyDoesNotExistError` if the object does not exist.
+    async fn get_object(&self, key: &str) -> Result<Vec<u8>, VaultError>;
+
+    /// Write data from a stream of chunks for the given object key, replacing any existing data.
+    ///
+    /// The default implementation collects all chunks and writes them with `put_object`.
+    async fn put_object_stream(
+        &self,
+        key: &str,
+        chunks: ObjectStream<'_>,
+    ) -> Result<(), VaultError> {
+        let chunks: Vec<Vec<u8>> = chunks.try_collect().await?;
+        self.put_object(key, chunks.concat()).await
+    }
+
+    /// Read data for the given object key as a stream of chunks.
+    ///
+    /// Returns `VaultError::KeyDoesNotExistError` if the object does not exist.
+    /// The default implementation reads the whole object with `get_object`.
+    async fn get_object_stream(&self, key: &str) -> Result<ObjectStream<'static>, VaultError> {
+        let data = self.get_object(key).await?;
+        Ok(Box::pin(futures::stream::once(async { Ok(data) })))
+    }
+
+    /// Copy data from the source object key to the destination key,
+    /// replacing any existing data.
+    ///
+    /// Returns `VaultError::KeyDoesNotExistError` if the source object does not exist.
+    /// The default implementation reads and writes the data.
+    async fn copy_object(&self, source: &str, destination: &str) -> Result<(), VaultError> {
+        let data = self.get_object(source).await?;
+        self.put_object(destination, data).await
+    }
+
+    /// Check if an object exists for the given key.
+    async fn object_exists(&self, key: &str) -> Result<bool, VaultError>;
+
+    /// Delete objects for the given keys.
+    ///
+    /// Keys that do not exist are ignored.
+    async fn delete_objects(&self, keys: &[String]) -> Result<(), VaultError>;
+
+    /// Delete objects for the given keys without stopping at the first failed key.
+    ///
+    /// Returns the keys that could not be deleted with the error for each key.
+    /// Keys that do not exist are ignored.
+    /// The default implementation deletes the objects one at a time.
+    async fn try_delete_objects(
+        &self,
+        keys: &[String],
+    ) -> Result<Vec<(String, VaultError)>, VaultError> {
+        let mut failed = Vec::new();
+        for key in keys {
+            if let Err(error) = self.delete_objects(std::slice::from_ref(key)).await {
+                failed.push((key.clone(), error));
+            }
+        }
+        Ok(failed)
+    }
+
+    /// Identifier for where the objects are stored, like the bucket name or root directory.
+    ///
+    /// Storages with the same location share the same objects.
+    /// The default implementation returns `None`, so no two storages are considered the same.
+    fn location(&self) -> Option<String> {
+        None
+    }
+
+    /// List all objects with keys starting with the given prefix.
+    ///
+    /// Returned keys include the prefix.
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<ObjectInfo>, VaultError>;
+
+    /// List objects directly under the given prefix using `/` as the delimiter.
+    ///
+    /// Keys continuing past the next `/` are grouped into common prefixes,
+    /// matching S3 listing with a delimiter.
+    /// The default implementation lists all objects under the prefix and groups them.
+    async fn list_directory(&self, prefix: &str) -> Result<DirectoryObjects, VaultError> {
+        let mut listing = DirectoryObjects::default();
+        for object in self.list_objects(prefix).await? {
+            let remainder = object.key.get(prefix.len()..).unwrap_or_default();
+            if let Some(index) = remainder.find('/') {
+                let common_prefix = format!("{prefix}{}", &remainder[..=index]);
+                if listing.prefixes.last() != Some(&common_prefix) {
+                    listing.prefixes.push(common_prefix);
+                }
+            } else {
+                listing.objects.push(object);
+            }
+        }
+        Ok(listing)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Metadata for a stored object returned from listing.
+pub struct ObjectInfo {
+    pub key: String,
+    /// Object size in bytes.
+    pub size: u64,
+    pub last_modified: Option<DateTime>,
+    /// Entity tag for the object data. Only available with S3.
+    pub etag: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+/// Result of listing objects with a delimiter.
+pub struct DirectoryObjects {
+    /// Objects directly under the listed prefix.
+    pub objects: Vec<ObjectInfo>,
+    /// Common prefixes ending with `/` for keys nested deeper.
+    pub prefixes: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+/// Storage backed by an S3 bucket.
+pub struct S3Storage {
+    client: S3Client,
+    bucket: String,
+}
+
+#[derive(Debug, Clone)]
+/// Storage backed by a directory on the local filesystem.
+///
+/// Each object is stored as a file under the root directory,
+/// with `/` in object keys mapped to subdirectories.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl S3Storage {
+    #[must_use]
+    pub fn new(client: S3Client, bucket: impl Into<String>) -> Self {
+        Self {
+            client,
+            bucket: bucket.into(),
+        }
+    }
+
+    async fn send_get_object(&self, key: &str) -> Result<GetObjectOutput, VaultError> {
+        self.client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|err| {
+                if let Some(service_error) = err.as_service_error() {
+                    if service_error.is_no_such_key() {
+                        VaultError::KeyDoesNotExistError
+                    } else {
+                        VaultError::S3GetObjectError(Box::new(err))
+                    }
+                } else {
+                    VaultError::S3GetObjectError(Box::new(err))
+                }
+            })
+    }
+
+    /// Upload parts of a started multipart upload and complete it.
+    ///
+    /// The first part has already been read from the stream.
+    async fn upload_parts(
+        &self,
+        key: &str,
+        upload_id: &str,
+        mut part: Vec<u8>,
+        mut chunks: ObjectStream<'_>,
+    ) -> Result<(), VaultError> {
+        let mut parts = Vec::new();
+        while !part.is_empty() {
+            if parts.len() >= MAX_MULTIPART_PARTS {
+                return Err(VaultError::S3UploadPartNumberError);
+            }
+            let part_number =
+                i32::try_from(parts.len() + 1).map_err(|_| VaultError::S3UploadPartNumberError)?;
+            let response = self
+                .client
+                .upload_part()
+                .bucket(&self.bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .body(ByteStream::from(part))
+                .send()
+                .await?;
+            parts.push(
+                CompletedPart::builder()
+                    .part_number(part_number)
+                    .set_e_tag(response.e_tag)
+                    .build(),
+            );
+            part = read_part(&mut chunks).await?;
+        }
+
+        self.client
+            .complete_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .multipart_upload(
+                CompletedMultipartUpload::builder()
+                    .set_parts(Some(parts))
+                    .build(),
+            )
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    /// Convert listed S3 object to `ObjectInfo`.
+    fn object_info(object: &Object) -> Option<ObjectInfo> {
+        object.key().map(|key| ObjectInfo {
+            key: key.to_owned(),
+            size: object
+                .size()
+                .and_then(|size| u64::try_from(size).ok())
+                .unwrap_or_default(),
+            last_modified: object.last_modified().copied(),
+            etag: object.e_tag().map(|etag| etag.trim_matches('"').to_owned()),
+        })
+    }
+}
+
+#[async_trait]
+impl Storage for S3Storage {
+    async fn put_object(&self, key: &str, data: Vec<u8>) -> Result<(), VaultError> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .acl(aws_sdk_s3::types::ObjectCannedAcl::Private)
+            .body(ByteStream::from(data))
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_object(&self, key: &str) -> Result<Vec<u8>, VaultError> {
+        let body = self
+            .send_get_object(key)
+            .await?
+            .body
+            .collect()
+            .await
+            .map_err(|_| VaultError::S3GetObjectBodyError)?;
+
+        Ok(body.to_vec())
+    }
+
+    /// Write large objects with a multipart upload so the whole object is never kept in memory.
+    ///
+    /// Objects smaller than one part are written with a single `PutObject` request.
+    async fn put_object_stream(
+        &self,
+        key: &str,
+        mut chunks: ObjectStream<'_>,
+    ) -> Result<(), VaultError> {
+        let first = read_part(&mut chunks).await?;
+        if first.len() < MULTIPART_PART_SIZE {
+            return self.put_object(key, first).await;
+        }
+
+        let upload_id = self
+            .client
+            .create_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .acl(ObjectCannedAcl::Private)
+            .send()
+            .await?
+            .upload_id
+            .ok_or(VaultError::S3MultipartUploadIdMissingError)?;
+
+        let result = self.upload_parts(key, &upload_id, first, chunks).await;
+        if result.is_err() {
+            // Remove uploaded parts, the upload error is more relevant than a failed abort
+            let _ = self
+                .client
+                .abort_multipart_upload()
+                .bucket(&self.bucket)
+                .key(key)
+                .upload_id(&upload_id)
+                .send()
+                .await;
+        }
+        result
+    }
+
+    async fn get_object_stream(&self, key: &str) -> Result<ObjectStream<'static>, VaultError> {
+        let body = self.send_get_object(key).await?.body;
+        Ok(Box::pin(futures::stream::try_unfold(
+            body,
+            |mut body| async move {
+                Ok(body
+                    .try_next()
+                    .await
+                    .map_err(|_| VaultError::S3GetObjectBodyError)?
+                    .map(|bytes| (bytes.to_vec(), body)))
+            },
+        )))
+    }
+
+    /// Copy object within the bucket without downloading the data.
+    async fn copy_object(&self, source: &str, destination: &str) -> Result<(), VaultError> {
+        self.client
+            .copy_object()
+            .bucket(&self.bucket)
+            .copy_source(format!("{}/{}", self.bucket, encode_copy_source(source)))
+            .key(destination)
+            .acl(aws_sdk_s3::types::ObjectCannedAcl::Private)
+            .send()
+            .await
+            .map_err(|err| {
+                if err.as_service_error().and_then(ProvideErrorMetadata::code) == Some("NoSuchKey")
+                {
+                    VaultError::KeyDoesNotExistError
+                } else {
+                    VaultError::S3CopyObjectError(Box::new(err))
+                }
+            })?;
+
+        Ok(())
+    }
+
+    async fn object_exists(&self, key: &str) -> Result<bool, VaultError> {
+        match self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                let service_error = e.into_service_error();
+                if service_error.is_not_found() {
+                    // The object does not exist
+                    Ok(false)
+                } else {
+                    // Propagate other errors like networking or permissions
+                    Err(VaultError::S3HeadObjectError(service_error))
+                }
+            }
+        }
+    }
+
+    async fn delete_objects(&self, keys: &[String]) -> Result<(), VaultError> {
+        match self.try_delete_objects(keys).await?.into_iter().next() {
+            Some((_, error)) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    async fn try_delete_objects(
+        &self,
+        keys: &[String],
+    ) -> Result<Vec<(String, VaultError)>, VaultError> {
+        let mut failed = Vec::new();
+        for batch in keys.chunks(MAX_DELETE_OBJECTS) {
+            let identifiers = batch
+                .iter()
+                .map(|key| {
+                    ObjectIdentifier::builder()
+                        .key(key)
+                        .build()
+                        .map_err(VaultError::from)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            // Quiet mode only returns the keys that failed
+            let response = self
+                .client
+                .delete_objects()
+                .bucket(&self.bucket)
+                .delete(
+                    Delete::builder()
+                        .set_objects(Some(identifiers))
+                        .quiet(true)
+                        .build()?,
+                )
+                .send()
+                .await?;
+
+            failed.extend(response.errors().iter().map(|error| {
+                let key = error.key().unwrap_or_default().to_string();
+                let message = error
+                    .message()
+                    .or_else(|| error.code())
+                    .unwrap_or("unknown error")
+                    .to_string();
+                (
+                    key.clone(),
+                    VaultError::S3DeleteObjectFailedError(key, message),
+                )
+            }));
+        }
+        Ok(failed)
+    }
+
+    fn location(&self) -> Option<String> {
+        Some(format!("s3://{}", self.bucket))
+    }
+
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<ObjectInfo>, VaultError> {
+        let mut objects = Vec::new();
+        let mut continuation_token: Option<String> = None;
+
+        // A single response contains at most 1000 objects
+        loop {
+            let response = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .set_prefix((!prefix.is_empty()).then(|| prefix.to_owned()))
+                .set_continuation_token(continuation_token.clone())
+                .send()
+                .await?;
+
+            objects.extend(response.contents().iter().filter_map(Self::object_info));
+
+            continuation_token = response.next_continuation_token().map(ToString::to_string);
+            if continuation_token.is_none() {
+                break;
+            }
+        }
+
+        Ok(objects)
+    }
+
+    async fn list_directory(&self, prefix: &str) -> Result<DirectoryObjects, VaultError> {
+        let mut listing = DirectoryObjects::default();
+        let mut continuation_token: Option<String> = None;
+
+        loop {
+            let response = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .set_prefix((!prefix.is_empty()).then(|| prefix.to_owned()))
+                .delimiter("/")
+                .set_continuation_token(continuation_token.clone())
+                .send()
+                .await?;
+
+            listing
+                .objects
+                .extend(response.contents().iter().filter_map(Self::object_info));
+            listing.prefixes.extend(
+                response
+                    .common_prefixes()
+                    .iter()
+                    .filter_map(|common_prefix| common_prefix.prefix().map(ToOwned::to_owned)),
+            );
+
+            continuation_token = response.next_continuation_token().map(ToString::to_string);
+            if continuation_token.is_none() {
+                break;
+            }
+        }
+
+        Ok(listing)
+    }
+}
+
+impl LocalStorage {
+    #[must_use]
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    #[must_use]
+    /// Root directory for stored objects.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Return file path for the given object key.
+    ///
+    /// Only plain path components are allowed so keys can't point outside the root directory.
+    fn object_path(&self, key: &str) -> Result<PathBuf, VaultError> {
+        let relative = Path::new(key);
+        if key.is_empty()
+            || !relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(VaultError::InvalidObjectKeyError(key.to_string()));
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put_object(&self, key: &str, data: Vec<u8>) -> Result<(), VaultError> {
+        let path = self.object_path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| VaultError::LocalStorageError(parent.display().to_string(), e))?;
+        }
+        tokio::fs::write(&path, data)
+            .await
+            .map_err(|e| VaultError::LocalStorageError(path.display().to_string(), e))
+    }
+
+    async fn get_object(&self, key: &str) -> Result<Vec<u8>, VaultError> {
+        let path = self.object_path(key)?;
+        tokio::fs::read(&path).await.map_err(|e| {
+            if e.kind() == io::ErrorKind::NotFound {
+                VaultError::KeyDoesNotExistError
+            } else {
+                VaultError::LocalStorageError(path.display().to_string(), e)
+            }
+        })
+    }
+
+    async fn put_object_stream(
+        &self,
+        key: &str,
+        mut chunks: ObjectStream<'_>,
+    ) -> Result<(), VaultError> {
+        let path = self.object_path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| VaultError::LocalStorageError(parent.display().to_string(), e))?;
+        }
+        let error = |e| VaultError::LocalStorageError(path.display().to_string(), e);
+        let mut file = tokio::fs::File::create(&path).await.map_err(error)?;
+        while let Some(chunk) = chunks.try_next().await? {
+            file.write_all(&chunk).await.map_err(error)?;
+        }
+        file.flush().await.map_err(error)
+    }
+
+    async fn get_object_stream(&self, key: &str) -> Result<ObjectStream<'static>, VaultError> {
+        let path = self.object_path(key)?;
+        let file = tokio::fs::File::open(&path).await.map_err(|e| {
+            if e.kind() == io::ErrorKind::NotFound {
+                VaultError::KeyDoesNotExistError
+            } else {
+                VaultError::LocalStorageError(path.display().to_string(), e)
+            }
+        })?;
+
+        Ok(Box::pin(futures::stream::try_unfold(
+            (file, path),
+            |(mut file, path)| async move {
+                let mut buffer = vec![0; LOCAL_READ_CHUNK_SIZE];
+                let length = file
+                    .read(&mut buffer)
+                    .await
+                    .map_err(|e| VaultError::LocalStorageError(path.display().to_string(), e))?;
+                if length == 0 {
+                    return Ok(None);
+                }
+                buffer.truncate(length);
+                Ok(Some((buffer, (file, path))))
+            },
+        )))
+    }
+
+    async fn copy_object(&self, source: &str, destination: &str) -> Result<(), VaultError> {
+        let source_path = self.object_path(source)?;
+        let path = self.object_path(destination)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| VaultError::LocalStorageError(parent.display().to_string(), e))?;
+        }
+        match tokio::fs::copy(&source_path, &path).await {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Err(VaultError::KeyDoesNotExistError),
+            Err(e) => Err(VaultError::LocalStorageError(path.display().to_string(), e)),
+        }
+    }
+
+    async fn object_exists(&self, key: &str) -> Result<bool, VaultError> {
+        let path = self.object_path(key)?;
+        tokio::fs::try_exists(&path)
+            .await
+            .map_err(|e| VaultError::LocalStorageError(path.display().to_string(), e))
+    }
+
+    async fn delete_objects(&self, keys: &[String]) -> Result<(), VaultError> {
+        for key in keys {
+            let path = self.object_path(key)?;
+            match tokio::fs::remove_file(&path).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(VaultError::LocalStorageError(path.display().to_string(), e));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn location(&self) -> Option<String> {
+        Some(self.root.display().to_string())
+    }
+
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<ObjectInfo>, VaultError> {
+        let mut objects = Vec::new();
+        if !tokio::fs::try_exists(&self.root)
+            .await
+            .map_err(|e| VaultError::LocalStorageError(self.root.display().to_string(), e))?
+        {
+            return Ok(objects);
+        }
+
+        let mut directories = vec![self.root.clone()];
+        while let Some(directory) = directories.pop() {
+            let mut entries = tokio::fs::read_dir(&directory)
+                .await
+                .map_err(|e| VaultError::LocalStorageError(directory.display().to_string(), e))?;
+            while let Some(entry) = entries
+                .next_entry()
+                .await
+                .map_err(|e| VaultError::LocalStorageError(directory.display().to_string(), e))?
+            {
+                let path = entry.path();
+                let metadata = entry
+                    .metadata()
+                    .await
+                    .map_err(|e| VaultError::LocalStorageError(path.display().to_string(), e))?;
+                if metadata.is_dir() {
+                    directories.push(path);
+                } else if let Ok(relative) = path.strip_prefix(&self.root) {
+                    // Object keys always use `/` as the separator regardless of platform
+                    let key = relative
+                        .components()
+                        .map(|component| component.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/");
+                    if key.starts_with(prefix) {
+                        objects.push(ObjectInfo {
+                            key,
+                            size: metadata.len(),
+                            last_modified: metadata.modified().ok().map(DateTime::from),
+                            etag: None,
+                        });
+                    }
+                }
+            }
+        }
+
+        // Match the lexicographic ordering of S3 listings
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+}
+
+/// Read chunks from the stream until there is enough data for a multipart upload part.
+///
+/// Returns an empty part when the stream has ended.
+async fn read_part(chunks: &mut ObjectStream<'_>) -> Result<Vec<u8>, VaultError> {
+    let mut part = Vec::new();
+    while part.len() < MULTIPART_PART_SIZE {
+        match chunks.try_next().await? {
+            Some(chunk) => part.extend_from_slice(&chunk),
+            None => break,
+        }
+    }
+    Ok(part)
+}
+
+/// Percent-encode object key for the S3 copy source, keeping `/` separators.
+fn encode_copy_source(key: &str) -> String {
+    key.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_copy_source, LocalStorage, Storage};
+    use crate::errors::VaultError;
+
+    fn temp_storage() -> LocalStorage {
+        let root = std::env::temp_dir().join(format!("nitor-vault-test-{}", rand::random::<u64>()));
+        LocalStorage::new(root)
+    }
+
+    async fn list_keys(storage: &LocalStorage, prefix: &str) -> Vec<String> {
+        storage
+            .list_objects(prefix)
+            .await
+            .expect("list failed")
+            .into_iter()
+            .map(|object| object.key)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn local_put_and_get() {
+        let storage = temp_storage();
+        storage
+            .put_object("team/secret.key", b"data".to_vec())
+            .await
+            .expect("put failed");
+
+        let data = storage
+            .get_object("team/secret.key")
+            .await
+            .expect("get failed");
+        assert_eq!(data, b"data");
+        assert!(storage
+            .object_exists("team/secret.key")
+            .await
+            .expect("exists failed"));
+
+        std::fs::remove_dir_all(storage.root()).expect("cleanup failed");
+    }
+
+    #[tokio::test]
+    async fn local_get_missing_key() {
+        let storage = temp_storage();
+        let result = storage.get_object("missing").await;
+        assert!(matches!(result, Err(VaultError::KeyDoesNotExistError)));
+        assert!(!storage
+            .object_exists("missing")
+            .await
+            .expect("exists failed"));
+    }
+
+    #[tokio::test]
+    async fn local_list_and_delete() {
+        let storage = temp_storage();
+        for key in ["b.meta", "a/c.key", "a.key"] {
+            storage
+                .put_object(key, Vec::new())
+                .await
+                .expect("put failed");
+        }
+
+        assert_eq!(
+            list_keys(&storage, "").await,
+            vec!["a.key", "a/c.key", "b.meta"]
+        );
+        assert_eq!(list_keys(&storage, "a/").await, vec!["a/c.key"]);
+
+        storage
+            .delete_objects(&["a/c.key".to_string(), "missing".to_string()])
+            .await
+            .expect("delete failed");
+        assert_eq!(list_keys(&storage, "").await, vec!["a.key", "b.meta"]);
+
+        std::fs::remove_dir_all(storage.root()).expect("cleanup failed");
+    }
+
+    #[tokio::test]
+    async fn local_list_directory() {
+        let storage = temp_storage();
+        for key in [
+            "a.key",
+            "team/b.key",
+            "team/c/d.key",
+            "team/c/e.key",
+            "team2/f.key",
+        ] {
+            storage
+                .put_object(key, Vec::new())
+                .await
+                .expect("put failed");
+        }
+
+        let listing = storage.list_directory("").await.expect("list failed");
+        let keys: Vec<_> = listing.objects.into_iter().map(|o| o.key).collect();
+        assert_eq!(keys, vec!["a.key"]);
+        assert_eq!(listing.prefixes, vec!["team/", "team2/"]);
+
+        let listing = storage.list_directory("team/").await.expect("list failed");
+        let keys: Vec<_> = listing.objects.into_iter().map(|o| o.key).collect();
+        assert_eq!(keys, vec!["team/b.key"]);
+        assert_eq!(listing.prefixes, vec!["team/c/"]);
+
+        std::fs::remove_dir_all(storage.root()).expect("cleanup failed");
+    }
+
+    #[tokio::test]
+    async fn local_copy_object() {
+        let storage = temp_storage();
+        storage
+            .put_object("a.key", b"data".to_vec())
+            .await
+            .expect("put failed");
+
+        storage
+            .copy_object("a.key", "nested/b.key")
+            .await
+            .expect("copy failed");
+        assert_eq!(
+            storage
+                .get_object("nested/b.key")
+                .await
+                .expect("get failed"),
+            b"data"
+        );
+
+        let result = storage.copy_object("missing.key", "c.key").await;
+        assert!(matches!(result, Err(VaultError::KeyDoesNotExistError)));
+
+        std::fs::remove_dir_all(storage.root()).expect("cleanup failed");
+    }
+
+    #[test]
+    fn copy_source_encoding() {
+        assert_eq!(encode_copy_source("team/db.key"), "team/db.key");
+        assert_eq!(encode_copy_source("my key+1ä"), "my%20key%2B1%C3%A4");
+    }
+
+    #[tokio::test]
+    async fn local_rejects_keys_outside_root() {
+        let storage = temp_storage();
+        for key in ["../escape", "/absolute", "a/../../b", ""] {
+            let result = storage.put_object(key, Vec::new()).await;
+            assert!(matches!(result, Err(VaultError::InvalidObjectKeyError(_))));
+        }
+    }
+}
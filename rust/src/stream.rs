@@ -0,0 +1,221 @@
+use aes_gcm::aead::consts::U12;
+use aes_gcm::aead::stream::{DecryptorBE32, EncryptorBE32};
+use aes_gcm::aead::Payload;
+use aes_gcm::aes::Aes256;
+use aes_gcm::{AesGcm, KeyInit};
+use futures::Stream;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::errors::VaultError;
+
+/// Algorithm name in the meta object for values encrypted in segments.
+pub const STREAM_ALGORITHM: &str = "AESGCM-STREAM";
+
+/// Plaintext size of each segment when storing values in the streaming format.
+pub const STREAM_SEGMENT_SIZE: usize = 1024 * 1024;
+
+/// Largest segment size accepted when decrypting, to limit memory use for invalid meta.
+const MAX_SEGMENT_SIZE: usize = 64 * 1024 * 1024;
+
+/// Length of the AES-GCM authentication tag added to each segment.
+const TAG_LENGTH: usize = 16;
+
+/// Length of the random nonce prefix.
+///
+/// The remaining 5 bytes of the 12-byte AES-GCM nonce are a 32-bit big-endian segment counter
+/// and a flag for the last segment, as in the STREAM construction.
+pub const NONCE_PREFIX_LENGTH: usize = 7;
+
+type Cipher = AesGcm<Aes256, U12>;
+
+/// Encrypt data from the reader in segments.
+///
+/// Each segment is authenticated with the given additional data,
+/// and the last segment is marked so truncated ciphertext fails to decrypt.
+pub fn encrypt_segments<'a>(
+    reader: impl AsyncRead + Unpin + Send + 'a,
+    data_key: &[u8],
+    nonce_prefix: [u8; NONCE_PREFIX_LENGTH],
+    aad: &'a [u8],
+) -> Result<impl Stream<Item = Result<Vec<u8>, VaultError>> + Send + 'a, VaultError> {
+    let encryptor = EncryptorBE32::from_aead(
+        Cipher::new_from_slice(data_key)?,
+        nonce_prefix.as_slice().into(),
+    );
+
+    Ok(futures::stream::try_unfold(
+        (reader, Some(encryptor), None),
+        move |(mut reader, encryptor, current)| async move {
+            let Some(mut encryptor) = encryptor else {
+                return Ok(None);
+            };
+            let current = match current {
+                Some(segment) => segment,
+                None => read_segment(&mut reader).await?,
+            };
+
+            // Read ahead to know if the current segment is the last one
+            let next = read_segment(&mut reader).await?;
+            let payload = Payload { msg: &current, aad };
+            if next.is_empty() {
+                let segment = encryptor
+                    .encrypt_last(payload)
+                    .map_err(|_| VaultError::CiphertextEncryptionError)?;
+                Ok(Some((segment, (reader, None, None))))
+            } else {
+                let segment = encryptor
+                    .encrypt_next(payload)
+                    .map_err(|_| VaultError::CiphertextEncryptionError)?;
+                Ok(Some((segment, (reader, Some(encryptor), Some(next)))))
+            }
+        },
+    ))
+}
+
+/// Read up to one segment of data, returning less only at the end of the data.
+async fn read_segment(reader: &mut (impl AsyncRead + Unpin)) -> Result<Vec<u8>, VaultError> {
+    let mut segment = Vec::with_capacity(STREAM_SEGMENT_SIZE);
+    reader
+        .take(STREAM_SEGMENT_SIZE as u64)
+        .read_to_end(&mut segment)
+        .await
+        .map_err(VaultError::StreamReadError)?;
+    Ok(segment)
+}
+
+/// Decrypts values stored in the streaming format as ciphertext is added.
+///
+/// Only authenticated plaintext is returned,
+/// and the last segment is only decrypted in `finish`.
+pub struct SegmentDecryptor<'a> {
+    decryptor: DecryptorBE32<Cipher>,
+    aad: &'a [u8],
+    encrypted_segment_size: usize,
+    buffer: Vec<u8>,
+}
+
+impl<'a> SegmentDecryptor<'a> {
+    /// Create decryptor for a value with the given nonce and segment size from the meta object.
+    pub fn new(
+        data_key: &[u8],
+        nonce: &[u8],
+        segment_size: Option<usize>,
+        aad: &'a [u8],
+    ) -> Result<Self, VaultError> {
+        let segment_size = segment_size
+            .filter(|size| (1..=MAX_SEGMENT_SIZE).contains(size))
+            .ok_or(VaultError::InvalidSegmentSizeError(segment_size))?;
+        let nonce_prefix = nonce
+            .get(..NONCE_PREFIX_LENGTH)
+            .ok_or(VaultError::NonceDecryptError)?;
+
+        Ok(Self {
+            decryptor: DecryptorBE32::from_aead(
+                Cipher::new_from_slice(data_key)?,
+                nonce_prefix.into(),
+            ),
+            aad,
+            encrypted_segment_size: segment_size + TAG_LENGTH,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Add ciphertext and return the plaintext of all complete segments before the last one.
+    pub fn update(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, VaultError> {
+        self.buffer.extend_from_slice(ciphertext);
+
+        let mut plaintext = Vec::new();
+        let mut offset = 0;
+        // The last segment is decrypted differently, so always keep one segment in the buffer
+        while self.buffer.len() - offset > self.encrypted_segment_size {
+            let segment = &self.buffer[offset..offset + self.encrypted_segment_size];
+            plaintext.extend(
+                self.decryptor
+                    .decrypt_next(Payload {
+                        msg: segment,
+                        aad: self.aad,
+                    })
+                    .map_err(|_| VaultError::NonceDecryptError)?,
+            );
+            offset += self.encrypted_segment_size;
+        }
+        self.buffer.drain(..offset);
+        Ok(plaintext)
+    }
+
+    /// Decrypt the last segment.
+    ///
+    /// Fails if the ciphertext was truncated.
+    pub fn finish(self) -> Result<Vec<u8>, VaultError> {
+        self.decryptor
+            .decrypt_last(Payload {
+                msg: &self.buffer,
+                aad: self.aad,
+            })
+            .map_err(|_| VaultError::NonceDecryptError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt;
+
+    use super::{encrypt_segments, SegmentDecryptor, STREAM_SEGMENT_SIZE};
+    use crate::errors::VaultError;
+
+    async fn encrypt(data: &[u8], key: &[u8; 32]) -> Vec<Vec<u8>> {
+        encrypt_segments(data, key, [7; 7], b"meta")
+            .expect("encryptor failed")
+            .try_collect()
+            .await
+            .expect("encrypt failed")
+    }
+
+    fn decrypt(
+        segments: &[Vec<u8>],
+        key: &[u8; 32],
+        chunk_size: usize,
+    ) -> Result<Vec<u8>, VaultError> {
+        let mut nonce = vec![7; 7];
+        nonce.extend([0; 5]);
+        let mut decryptor = SegmentDecryptor::new(key, &nonce, Some(STREAM_SEGMENT_SIZE), b"meta")?;
+        let mut plaintext = Vec::new();
+        for chunk in segments.concat().chunks(chunk_size) {
+            plaintext.extend(decryptor.update(chunk)?);
+        }
+        plaintext.extend(decryptor.finish()?);
+        Ok(plaintext)
+    }
+
+    #[tokio::test]
+    async fn segments_roundtrip() {
+        let key = [1; 32];
+        for length in [0, 10, STREAM_SEGMENT_SIZE, 2 * STREAM_SEGMENT_SIZE + 5] {
+            let data: Vec<u8> = (0..=250).cycle().take(length).collect();
+            let segments = encrypt(&data, &key).await;
+            assert_eq!(segments.len(), length.div_ceil(STREAM_SEGMENT_SIZE).max(1));
+            for chunk_size in [1000, STREAM_SEGMENT_SIZE + 16, 3 * STREAM_SEGMENT_SIZE] {
+                assert_eq!(
+                    decrypt(&segments, &key, chunk_size).expect("decrypt failed"),
+                    data
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn truncated_or_reordered_segments_fail() {
+        let key = [1; 32];
+        let data = vec![0; 3 * STREAM_SEGMENT_SIZE];
+        let segments = encrypt(&data, &key).await;
+
+        assert!(decrypt(&segments[..2], &key, 1000).is_err());
+        let reordered = [
+            segments[1].clone(),
+            segments[0].clone(),
+            segments[2].clone(),
+        ];
+        assert!(decrypt(&reordered, &key, 1000).is_err());
+        assert!(decrypt(&segments, &[2; 32], 1000).is_err());
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Key names grouped into a tree by the `/` separator.
+pub struct KeyTree {
+    /// Path segment for this node.
+    /// Directories end with `/`, the root node name is the listed path.
+    pub name: String,
+    /// Number of keys in this subtree.
+    pub count: usize,
+    /// Child directories and keys, directories first and each sorted by name.
+    pub children: Vec<Self>,
+}
+
+#[derive(Default)]
+/// Intermediate tree used for grouping keys before sorting children.
+struct Node {
+    directories: BTreeMap<String, Self>,
+    keys: Vec<String>,
+}
+
+impl KeyTree {
+    #[must_use]
+    /// Build a tree from key names relative to the given root path.
+    pub fn from_keys<S: AsRef<str>>(root: &str, keys: &[S]) -> Self {
+        let mut node = Node::default();
+        for key in keys {
+            let mut current = &mut node;
+            let mut segments = key.as_ref().split_inclusive('/').peekable();
+            while let Some(segment) = segments.next() {
+                if segments.peek().is_some() {
+                    current = current.directories.entry(segment.to_string()).or_default();
+                } else if segment.ends_with('/') {
+                    // Key ending with a separator, treat as an empty directory
+                    current.directories.entry(segment.to_string()).or_default();
+                } else {
+                    current.keys.push(segment.to_string());
+                }
+            }
+        }
+        node.into_tree(root.to_string())
+    }
+
+    #[must_use]
+    /// Return true if this node is a directory.
+    pub fn is_directory(&self) -> bool {
+        self.name.ends_with('/')
+    }
+
+    /// Write the tree with box-drawing characters, limited to the given depth.
+    fn write_children(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        indent: &str,
+        depth: Option<usize>,
+    ) -> fmt::Result {
+        if depth == Some(0) {
+            return Ok(());
+        }
+        let depth = depth.map(|depth| depth - 1);
+        for (index, child) in self.children.iter().enumerate() {
+            let last = index + 1 == self.children.len();
+            let (branch, next_indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            if child.is_directory() {
+                writeln!(f, "{indent}{branch}{} ({})", child.name, child.count)?;
+                child.write_children(f, &format!("{indent}{next_indent}"), depth)?;
+            } else {
+                writeln!(f, "{indent}{branch}{}", child.name)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[must_use]
+    /// Return a displayable tree limited to the given depth.
+    pub const fn display(&self, depth: Option<usize>) -> KeyTreeDisplay<'_> {
+        KeyTreeDisplay { tree: self, depth }
+    }
+}
+
+impl Node {
+    fn into_tree(mut self, name: String) -> KeyTree {
+        self.keys.sort();
+        let mut children: Vec<KeyTree> = self
+            .directories
+            .into_iter()
+            .map(|(name, node)| node.into_tree(name))
+            .collect();
+        children.extend(self.keys.into_iter().map(|key| KeyTree {
+            name: key,
+            count: 1,
+            children: Vec::new(),
+        }));
+
+        KeyTree {
+            name,
+            count: children.iter().map(|child| child.count).sum(),
+            children,
+        }
+    }
+}
+
+/// Helper for displaying a `KeyTree` with a depth limit.
+pub struct KeyTreeDisplay<'a> {
+    tree: &'a KeyTree,
+    depth: Option<usize>,
+}
+
+impl fmt::Display for KeyTreeDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = if self.tree.name.is_empty() {
+            "."
+        } else {
+            &self.tree.name
+        };
+        writeln!(f, "{name} ({})", self.tree.count)?;
+        self.tree.write_children(f, "", self.depth)
+    }
+}
+
+impl fmt::Display for KeyTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.display(None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KeyTree;
+
+    #[test]
+    fn from_keys_groups_by_separator() {
+        let tree = KeyTree::from_keys("", &["b", "team/x", "team/svc/y", "a", "team/svc/z"]);
+        assert_eq!(tree.count, 5);
+
+        let names: Vec<&str> = tree.children.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["team/", "a", "b"]);
+
+        let team = &tree.children[0];
+        assert_eq!(team.count, 3);
+        assert_eq!(team.children[0].name, "svc/");
+        assert_eq!(team.children[0].count, 2);
+        assert_eq!(team.children[1].name, "x");
+    }
+
+    #[test]
+    fn display_with_depth() {
+        let tree = KeyTree::from_keys("prod/", &["app/db", "app/api", "key"]);
+        assert_eq!(
+            tree.to_string(),
+            "prod/ (3)\n├── app/ (2)\n│   ├── api\n│   └── db\n└── key\n"
+        );
+        assert_eq!(
+            tree.display(Some(1)).to_string(),
+            "prod/ (3)\n├── app/ (2)\n└── key\n"
+        );
+    }
+}
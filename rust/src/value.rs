@@ -142,6 +142,76 @@ impl Value {
         writer.flush()
     }
 
+    /// Parse value as JSON.
+    pub fn to_json(&self) -> Result<serde_json::Value, VaultError> {
+        match self {
+            Self::Utf8(string) => serde_json::from_str(string)
+                .map_err(|error| VaultError::InvalidJsonError(error.to_string())),
+            Self::Binary(_) => Err(VaultError::InvalidJsonError("binary data".to_string())),
+        }
+    }
+
+    /// Get a field from a JSON value.
+    ///
+    /// The field is either a top-level key, or a JSON Pointer path starting with `/`.
+    pub fn json_field(&self, field: &str) -> Result<serde_json::Value, VaultError> {
+        let json = self.to_json()?;
+        let value = if field.starts_with('/') {
+            json.pointer(field)
+        } else {
+            json.get(field)
+        };
+        value
+            .cloned()
+            .ok_or_else(|| VaultError::JsonFieldNotFoundError(field.to_string()))
+    }
+
+    /// Set a field in a JSON value and return the updated value.
+    ///
+    /// The field is either a top-level key, or a JSON Pointer path starting with `/`.
+    /// A missing field is added to its parent, but the parent needs to exist.
+    /// The updated JSON is pretty-printed if the original value spans multiple lines.
+    pub fn with_json_field(
+        &self,
+        field: &str,
+        value: serde_json::Value,
+    ) -> Result<Self, VaultError> {
+        let mut json = self.to_json()?;
+        let (parent, name) = if field.starts_with('/') {
+            let (parent, name) = field.rsplit_once('/').unwrap_or(("", field));
+            (
+                json.pointer_mut(parent),
+                name.replace("~1", "/").replace("~0", "~"),
+            )
+        } else {
+            (Some(&mut json), field.to_string())
+        };
+
+        let not_found = || VaultError::JsonFieldNotFoundError(field.to_string());
+        match parent {
+            Some(serde_json::Value::Object(object)) => {
+                object.insert(name, value);
+            }
+            Some(serde_json::Value::Array(array)) if name == "-" => array.push(value),
+            Some(serde_json::Value::Array(array)) => {
+                let element = name
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| array.get_mut(index))
+                    .ok_or_else(not_found)?;
+                *element = value;
+            }
+            _ => return Err(not_found()),
+        }
+
+        let json = if self.as_bytes().contains(&b'\n') {
+            serde_json::to_string_pretty(&json)?
+        } else {
+            serde_json::to_string(&json)?
+        };
+        Ok(Self::Utf8(json))
+    }
+
     #[must_use]
     /// Try to decode UTF-8 string from base64.
     pub fn decode_base64(self) -> Self {
@@ -183,8 +253,62 @@ impl fmt::Display for Value {
 #[cfg(test)]
 mod tests {
     use super::Value;
+    use crate::errors::VaultError;
     use base64::Engine;
 
+    #[test]
+    fn json_field_and_pointer() {
+        let value = Value::Utf8(
+            r#"{"user": "app", "password": "old", "replicas": [{"port": 5432}]}"#.to_string(),
+        );
+        assert_eq!(
+            value.json_field("password").expect("field failed"),
+            serde_json::json!("old")
+        );
+        assert_eq!(
+            value.json_field("/replicas/0/port").expect("field failed"),
+            serde_json::json!(5432)
+        );
+        assert!(matches!(
+            value.json_field("missing"),
+            Err(VaultError::JsonFieldNotFoundError(_))
+        ));
+        assert!(matches!(
+            Value::Utf8("not json".to_string()).json_field("password"),
+            Err(VaultError::InvalidJsonError(_))
+        ));
+    }
+
+    #[test]
+    fn with_json_field_keeps_order() {
+        let value =
+            Value::Utf8(r#"{"user": "app", "password": "old", "replicas": [{}]}"#.to_string());
+        let updated = value
+            .with_json_field("password", serde_json::json!("new"))
+            .expect("set failed")
+            .with_json_field("/replicas/0/port", serde_json::json!(5433))
+            .expect("set failed")
+            .with_json_field("/replicas/-", serde_json::json!({}))
+            .expect("set failed");
+        assert_eq!(
+            updated.to_string(),
+            r#"{"user":"app","password":"new","replicas":[{"port":5433},{}]}"#
+        );
+        assert!(matches!(
+            value.with_json_field("/missing/port", serde_json::json!(1)),
+            Err(VaultError::JsonFieldNotFoundError(_))
+        ));
+        assert!(matches!(
+            value.with_json_field("/replicas/5", serde_json::json!(1)),
+            Err(VaultError::JsonFieldNotFoundError(_))
+        ));
+
+        let pretty = Value::Utf8("{\n  \"a\": 1\n}".to_string())
+            .with_json_field("b", serde_json::json!(2))
+            .expect("set failed");
+        assert_eq!(pretty.to_string(), "{\n  \"a\": 1,\n  \"b\": 2\n}");
+    }
+
     #[test]
     fn new_valid_utf8() {
         let input = b"Hello, world!".to_vec();
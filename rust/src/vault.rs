@@ -1,4 +1,6 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
+use std::time::{Duration, SystemTime};
 
 use aes_gcm::aead::consts::U12;
 use aes_gcm::aead::{Aead, Payload};
@@ -7,23 +9,62 @@ use aes_gcm::{AesGcm, KeyInit, Nonce};
 use aws_config::Region;
 use aws_sdk_cloudformation::types::{Capability, Parameter, StackStatus};
 use aws_sdk_cloudformation::Client as CloudFormationClient;
-use aws_sdk_kms::primitives::Blob;
-use aws_sdk_kms::types::DataKeySpec;
 use aws_sdk_kms::Client as KmsClient;
-use aws_sdk_s3::operation::put_object::PutObjectOutput;
-use aws_sdk_s3::primitives::ByteStream;
-use aws_sdk_s3::types::Delete;
+use aws_sdk_s3::primitives::DateTime;
 use aws_sdk_s3::Client as S3Client;
 use aws_sdk_sts::Client as stsClient;
 use base64::Engine;
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use futures::{StreamExt, TryStreamExt};
 use rand::Rng;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::sync::OnceCell;
 
+use crate::archive::{Archive, ArchivedSecret, ImportPolicy, ImportResult};
 use crate::cloudformation;
 use crate::cloudformation::{CloudFormationParams, CloudFormationStackData};
+use crate::compression::{Compression, ZSTD_ALGORITHM};
 use crate::errors::VaultError;
+use crate::key_provider::{
+    CachingKeyProvider, DataKeyCacheLimits, KeyProvider, KmsKeyProvider, LocalKeyProvider,
+};
+use crate::storage::{LocalStorage, ObjectInfo, S3Storage, Storage, MAX_DELETE_OBJECTS};
+use crate::stream::{
+    encrypt_segments, SegmentDecryptor, NONCE_PREFIX_LENGTH, STREAM_ALGORITHM, STREAM_SEGMENT_SIZE,
+};
 use crate::template::{template, VAULT_STACK_VERSION};
 use crate::value::Value;
-use crate::{CreateStackResult, EncryptObject, Meta, S3DataKeys, UpdateStackResult, VaultConfig};
+use crate::{
+    BulkResult, CreateStackResult, DirectoryCount, DirectoryListing, EncryptObject, KeyMatcher,
+    KeyTree, Meta, S3DataKeys, SecretFormat, SecretInfo, SecretMetadata, SecretObjects,
+    UpdateStackResult, VaultConfig, VaultDiff,
+};
+
+/// Return the longest common prefix of the given strings.
+fn common_prefix(names: &[String]) -> &str {
+    let Some((first, rest)) = names.split_first() else {
+        return "";
+    };
+    let mut length = rest.iter().fold(first.len(), |length, name| {
+        first
+            .bytes()
+            .zip(name.bytes())
+            .take(length)
+            .take_while(|(a, b)| a == b)
+            .count()
+    });
+    while !first.is_char_boundary(length) {
+        length -= 1;
+    }
+    &first[..length]
+}
+
+/// AES-CTR cipher used by the old deprecated encryption method.
+type LegacyCipher = ctr::Ctr128BE<Aes256>;
+
+/// Static initialization vector used by the old deprecated encryption method.
+const LEGACY_STATIC_IV: [u8; 16] = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 5, 57];
 
 #[derive(Debug)]
 pub struct Vault {
@@ -34,11 +75,17 @@ pub struct Vault {
     pub prefix: String,
     pub cloudformation_params: CloudFormationParams,
     cf: CloudFormationClient,
-    kms: KmsClient,
-    s3: S3Client,
+    keys: Box<dyn KeyProvider>,
+    storage: Box<dyn Storage>,
+    /// Used for looking up the creator of new secrets, not set when running fully offline.
+    sts: Option<stsClient>,
+    caller_arn: OnceCell<Option<String>>,
 }
 
 impl Vault {
+    /// Default number of concurrent requests for bulk operations.
+    pub const DEFAULT_CONCURRENCY: usize = 16;
+
     /// Construct Vault for an existing vault stack with defaults.
     ///
     /// This will try reading environment variables for the config values,
@@ -63,57 +110,122 @@ impl Vault {
         iam_id: Option<String>,
         iam_secret: Option<String>,
     ) -> Result<Self, VaultError> {
+        Self::from_config(VaultConfig {
+            vault_stack,
+            region,
+            bucket,
+            key,
+            prefix,
+            profile,
+            iam_id,
+            iam_secret,
+            local_dir: None,
+            local_key: None,
+            data_key_cache: None,
+            ignore_env: false,
+        })
+        .await
+    }
+
+    /// Construct Vault for an existing vault stack from given `VaultConfig`.
+    ///
+    /// This will try reading environment variables for the config values that are `None`,
+    /// unless `ignore_env` is set.
+    pub async fn from_config(config: VaultConfig) -> Result<Self, VaultError> {
+        // Check env variables directly in case the library is not used through the CLI.
+        // These are also handled in the CLI, so they are documented in the CLI help.
+        let VaultConfig {
+            vault_stack,
+            region,
+            bucket,
+            key,
+            prefix,
+            profile,
+            iam_id,
+            iam_secret,
+            local_dir,
+            local_key,
+            data_key_cache,
+            ignore_env: _,
+        } = config.with_env_defaults();
+
         let config = crate::resolve_aws_config_from_args(region, profile, iam_id, iam_secret).await;
         let region = config
             .region()
             .map(ToOwned::to_owned)
             .ok_or_else(|| VaultError::NoRegionError)?;
 
-        // Check env variables directly in case the library is not used through the CLI.
-        // These are also handled in the CLI, so they are documented in the CLI help.
-        let stack_name = vault_stack
-            .or_else(|| crate::get_env_variable("VAULT_STACK"))
-            .unwrap_or_else(|| "vault".to_string());
-        let bucket = bucket.or_else(|| crate::get_env_variable("VAULT_BUCKET"));
-        let key = key.or_else(|| crate::get_env_variable("VAULT_KEY"));
-        let mut prefix = prefix
-            .or_else(|| crate::get_env_variable("VAULT_PREFIX"))
-            .unwrap_or_default();
+        let stack_name = vault_stack.unwrap_or_else(|| "vault".to_string());
+        let mut prefix = prefix.unwrap_or_default();
 
         if !prefix.is_empty() && !prefix.ends_with('/') {
             prefix.push('/');
         }
 
         let cf_client = CloudFormationClient::new(&config);
-        let cloudformation_params = if let (Some(bucket), Some(key)) = (bucket, key) {
-            CloudFormationParams::new(bucket, Some(key), stack_name)
-        } else {
-            CloudFormationParams::from_stack(&cf_client, stack_name).await?
+        let cloudformation_params = match (bucket, key) {
+            (Some(bucket), Some(key)) => CloudFormationParams::new(bucket, Some(key), stack_name),
+            // Local storage and keys should work offline, so don't require the CloudFormation stack
+            (bucket, key) if local_dir.is_some() || (local_key.is_some() && bucket.is_some()) => {
+                CloudFormationParams::new(bucket.unwrap_or_default(), key, stack_name)
+            }
+            _ => CloudFormationParams::from_stack(&cf_client, stack_name).await?,
+        };
+
+        let sts = (local_dir.is_none() || local_key.is_none()).then(|| stsClient::new(&config));
+
+        let storage: Box<dyn Storage> = match local_dir {
+            Some(dir) => Box::new(LocalStorage::new(dir)),
+            None => Box::new(S3Storage::new(
+                S3Client::new(&config),
+                &cloudformation_params.bucket_name,
+            )),
+        };
+
+        let mut keys: Box<dyn KeyProvider> = match local_key {
+            Some(path) => Box::new(LocalKeyProvider::from_file(path)?),
+            None => Box::new(KmsKeyProvider::new(
+                KmsClient::new(&config),
+                cloudformation_params.key_arn.clone(),
+            )),
         };
+        if let Some(limits) = data_key_cache {
+            keys = Box::new(CachingKeyProvider::from_boxed(keys, limits));
+        }
 
         Ok(Self {
             region,
             prefix,
             cloudformation_params,
             cf: cf_client,
-            kms: KmsClient::new(&config),
-            s3: S3Client::new(&config),
+            keys,
+            storage,
+            sts,
+            caller_arn: OnceCell::new(),
         })
     }
 
-    /// Construct Vault for an existing vault stack from given `VaultConfig`.
-    pub async fn from_config(config: VaultConfig) -> Result<Self, VaultError> {
-        Self::new(
-            config.vault_stack,
-            config.region,
-            config.bucket,
-            config.key,
-            config.prefix,
-            config.profile,
-            config.iam_id,
-            config.iam_secret,
-        )
-        .await
+    #[must_use]
+    /// Use the given storage backend for the encrypted data instead of the S3 bucket.
+    pub fn with_storage(mut self, storage: impl Storage + 'static) -> Self {
+        self.storage = Box::new(storage);
+        self
+    }
+
+    #[must_use]
+    /// Use the given key provider for encryption instead of the KMS key.
+    pub fn with_key_provider(mut self, keys: impl KeyProvider + 'static) -> Self {
+        self.keys = Box::new(keys);
+        self
+    }
+
+    #[must_use]
+    /// Cache data keys of the current key provider with the given limits.
+    ///
+    /// See [`CachingKeyProvider`] for details.
+    pub fn with_data_key_cache(mut self, limits: DataKeyCacheLimits) -> Self {
+        self.keys = Box::new(CachingKeyProvider::from_boxed(self.keys, limits));
+        self
     }
 
     /// Initialize new Vault stack.
@@ -248,29 +360,86 @@ impl Vault {
 
     /// Get all available secrets.
     ///
+    /// Only keys under the configured prefix are listed,
+    /// and the prefix is removed from the returned key names.
+    ///
     /// Returns a list of key names.
     pub async fn all(&self) -> Result<Vec<String>, VaultError> {
-        let output = self
-            .s3
-            .list_objects_v2()
-            .bucket(&self.cloudformation_params.bucket_name)
-            .send()
-            .await?;
+        self.names_under("").await
+    }
 
-        Ok(output
-            .contents()
+    /// Get all secrets with key names matching the given glob pattern or regex.
+    ///
+    /// Glob patterns only list keys under the literal path of the pattern.
+    /// The prefix is not part of the matched key names.
+    pub async fn all_matching(&self, matcher: &KeyMatcher) -> Result<Vec<String>, VaultError> {
+        Ok(self
+            .list_objects(matcher.path_prefix())
+            .await?
             .iter()
-            .filter_map(|object| -> Option<String> {
-                object.key().and_then(|key| {
-                    if key.ends_with(".aesgcm.encrypted") {
-                        key.strip_suffix(".aesgcm.encrypted")
-                            .map(std::borrow::ToOwned::to_owned)
-                    } else {
-                        None
-                    }
+            .filter_map(|object| object.key.strip_suffix(".aesgcm.encrypted"))
+            .filter(|name| matcher.is_match(name))
+            .map(ToOwned::to_owned)
+            .collect())
+    }
+
+    /// List keys and nested paths directly under the given path.
+    ///
+    /// The path should be empty for the top level, or end with `/`.
+    /// All keys under the path are listed to count the keys under each nested path.
+    /// Returned names are full key names without the prefix.
+    pub async fn list_directory(&self, path: &str) -> Result<DirectoryListing, VaultError> {
+        let (directories, keys): (Vec<KeyTree>, Vec<KeyTree>) = self
+            .tree(path)
+            .await?
+            .children
+            .into_iter()
+            .partition(KeyTree::is_directory);
+
+        Ok(DirectoryListing {
+            keys: keys
+                .into_iter()
+                .map(|key| format!("{path}{}", key.name))
+                .collect(),
+            directories: directories
+                .into_iter()
+                .map(|directory| DirectoryCount {
+                    path: format!("{path}{}", directory.name),
+                    count: directory.count,
                 })
-            })
-            .collect::<Vec<_>>())
+                .collect(),
+        })
+    }
+
+    /// Get all keys under the given path grouped into a tree by the `/` separator.
+    ///
+    /// Each directory in the tree has the number of keys in its subtree.
+    pub async fn tree(&self, path: &str) -> Result<KeyTree, VaultError> {
+        let keys: Vec<String> = self
+            .list_objects(path)
+            .await?
+            .iter()
+            .filter_map(|object| object.key.strip_prefix(path))
+            .filter_map(|key| key.strip_suffix(".aesgcm.encrypted"))
+            .map(ToOwned::to_owned)
+            .collect();
+
+        Ok(KeyTree::from_keys(path, &keys))
+    }
+
+    /// Get information for all stored secrets, including incomplete and deprecated ones.
+    ///
+    /// Uses only the storage object metadata, so nothing is decrypted.
+    pub async fn all_info(&self) -> Result<Vec<SecretInfo>, VaultError> {
+        let mut secrets = BTreeMap::new();
+        for object in self.list_objects("").await? {
+            SecretObjects::insert(&mut secrets, object);
+        }
+
+        Ok(secrets
+            .into_iter()
+            .map(|(name, objects)| objects.into_info(name))
+            .collect())
     }
 
     /// Get Cloudformation parameters.
@@ -284,46 +453,508 @@ impl Vault {
     /// Returns `true` if the key exists, `false` otherwise.
     pub async fn exists(&self, name: &str) -> Result<bool, VaultError> {
         let name = self.full_key_name(name);
-        match self
-            .s3
-            .head_object()
-            .bucket(self.cloudformation_params.bucket_name.clone())
-            .key(format!("{name}.key"))
-            .send()
+        self.storage.object_exists(&format!("{name}.key")).await
+    }
+
+    /// Store encrypted data with given key name in storage.
+    ///
+    /// Existing metadata and compression are kept when overwriting a key.
+    pub async fn store(&self, name: &str, data: &[u8]) -> Result<(), VaultError> {
+        self.store_with_metadata(name, data, SecretMetadata::default())
+            .await
+    }
+
+    /// Encrypt data with metadata and store it in S3 under the given key name.
+    ///
+    /// When overwriting a key, the given fields replace existing values and tags are merged.
+    /// Timestamps and the creator are set automatically.
+    /// A compressed key stays compressed with the same codec.
+    pub async fn store_with_metadata(
+        &self,
+        name: &str,
+        data: &[u8],
+        metadata: SecretMetadata,
+    ) -> Result<(), VaultError> {
+        let (metadata, compression) = self.merged_metadata(name, metadata).await?;
+        self.write(name, data, metadata, compression).await
+    }
+
+    /// Compress data with the given codec, then encrypt and store it like
+    /// [`Self::store_with_metadata`].
+    ///
+    /// The codec is recorded in the meta object and the value is decompressed on lookup.
+    /// Data that does not get smaller is stored uncompressed.
+    /// Compressed values are stored with the `AESGCM-ZSTD` algorithm,
+    /// which older vault versions can't decrypt.
+    pub async fn store_compressed(
+        &self,
+        name: &str,
+        data: &[u8],
+        metadata: SecretMetadata,
+        compression: Compression,
+    ) -> Result<(), VaultError> {
+        let (metadata, _) = self.merged_metadata(name, metadata).await?;
+        self.write(name, data, metadata, Some(compression)).await
+    }
+
+    /// Store a value for a key that is known not to exist yet,
+    /// for example after checking with [`Self::exists`].
+    ///
+    /// Skips reading the existing metadata like [`Self::store_with_metadata`] does.
+    /// An existing key is overwritten without keeping its metadata or compression.
+    pub async fn store_new(
+        &self,
+        name: &str,
+        data: &[u8],
+        metadata: SecretMetadata,
+        compression: Option<Compression>,
+    ) -> Result<(), VaultError> {
+        let metadata = self.new_metadata(metadata).await;
+        self.write(name, data, metadata, compression).await
+    }
+
+    /// Encrypt data from the reader in segments and store it under the given key name.
+    ///
+    /// The data is never fully held in memory:
+    /// it is encrypted in segments of 1 MiB and uploaded to S3 with multipart upload.
+    /// Values are stored with the `AESGCM-STREAM` algorithm,
+    /// which older vault versions can't decrypt.
+    /// Metadata is merged like in [`Self::store_with_metadata`].
+    pub async fn store_stream(
+        &self,
+        name: &str,
+        reader: impl AsyncRead + Unpin + Send,
+        metadata: SecretMetadata,
+    ) -> Result<(), VaultError> {
+        let (metadata, _) = self.merged_metadata(name, metadata).await?;
+
+        // The data length is not known beforehand, so never reuse a cached data key
+        let data_key = self.keys.generate_data_key_for(usize::MAX).await?;
+        let mut nonce_prefix = [0; NONCE_PREFIX_LENGTH];
+        rand::thread_rng().fill(&mut nonce_prefix);
+        // Stored as a full 12-byte nonce so older versions fail to authenticate the value
+        let nonce = [nonce_prefix.as_slice(), &[0; 5]].concat();
+        let meta = Meta::aesgcm_stream(&nonce, STREAM_SEGMENT_SIZE, metadata).to_json()?;
+        let segments =
+            encrypt_segments(reader, &data_key.plaintext, nonce_prefix, meta.as_bytes())?;
+
+        let key = &self.full_key_name(name);
+        let keys = S3DataKeys::new(key);
+
+        let put_cipher = self
+            .storage
+            .put_object_stream(&keys.cipher, Box::pin(segments));
+        let put_key = self.storage.put_object(&keys.key, data_key.ciphertext);
+        let put_meta = self
+            .storage
+            .put_object(&keys.meta, meta.as_bytes().to_vec());
+
+        tokio::try_join!(put_cipher, put_key, put_meta)?;
+
+        Ok(())
+    }
+
+    /// Merge the given metadata with the existing metadata of a key.
+    ///
+    /// Also returns the codec the existing value was compressed with.
+    async fn merged_metadata(
+        &self,
+        name: &str,
+        metadata: SecretMetadata,
+    ) -> Result<(SecretMetadata, Option<Compression>), VaultError> {
+        let keys = S3DataKeys::new(&self.full_key_name(name));
+        match self.storage.get_object(&keys.meta).await {
+            Ok(existing) => {
+                let meta: Meta = serde_json::from_slice(&existing)?;
+                let compression = meta.compression();
+                let mut merged = meta.metadata;
+                merged.update(metadata);
+                merged.updated = crate::format_timestamp(DateTime::from(SystemTime::now()));
+                Ok((merged, compression))
+            }
+            Err(VaultError::KeyDoesNotExistError) => Ok((self.new_metadata(metadata).await, None)),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Metadata for a new key with the creation time and creator set.
+    async fn new_metadata(&self, metadata: SecretMetadata) -> SecretMetadata {
+        let now = crate::format_timestamp(DateTime::from(SystemTime::now()));
+        let mut created = SecretMetadata {
+            created: now.clone(),
+            created_by: self.caller_arn().await,
+            ..SecretMetadata::default()
+        };
+        created.update(metadata);
+        created.updated = now;
+        created
+    }
+
+    /// Set a field in a JSON secret and store the updated value.
+    ///
+    /// The field is either a top-level key, or a JSON Pointer path starting with `/`.
+    /// The secret is decrypted, patched and stored again with its existing metadata.
+    /// Fails without storing if the version of the secret changed after it was decrypted.
+    /// The check is best-effort: a write right after the check can still be overwritten,
+    /// since the secret is stored as multiple objects that can't be replaced atomically.
+    pub async fn set_field(
+        &self,
+        name: &str,
+        field: &str,
+        value: serde_json::Value,
+    ) -> Result<(), VaultError> {
+        let version = self.version(name).await?;
+        let current = Box::pin(self.lookup(name)).await?;
+        let updated = current.with_json_field(field, value)?;
+        if self.version(name).await? != version {
+            return Err(VaultError::ConcurrentModificationError(name.to_string()));
+        }
+        self.store_with_metadata(name, updated.as_bytes(), SecretMetadata::default())
             .await
+    }
+
+    /// Compare secrets under the given path with secrets under the other path in another vault.
+    ///
+    /// The other vault can be this same vault.
+    /// Key names in the result are relative to the compared paths.
+    /// Values are decrypted from both vaults and compared by their SHA-256 hashes.
+    /// Secrets using the deprecated encryption method are compared the same way.
+    /// Metadata is not compared.
+    pub async fn diff(
+        &self,
+        path: &str,
+        other: &Self,
+        other_path: &str,
+    ) -> Result<VaultDiff, VaultError> {
+        let (formats, other_formats) =
+            tokio::try_join!(self.formats_under(path), other.formats_under(other_path))?;
+        let relative = |formats: BTreeMap<String, SecretFormat>, path: &str| {
+            formats
+                .into_iter()
+                .filter_map(|(name, format)| Some((name.strip_prefix(path)?.to_owned(), format)))
+                .collect::<BTreeMap<_, _>>()
+        };
+        let formats = relative(formats, path);
+        let other_formats = relative(other_formats, other_path);
+
+        let mut diff = VaultDiff {
+            only_other: other_formats
+                .keys()
+                .filter(|name| !formats.contains_key(*name))
+                .cloned()
+                .collect(),
+            ..VaultDiff::default()
+        };
+        for (name, format) in formats {
+            let Some(&other_format) = other_formats.get(&name) else {
+                diff.only_self.push(name);
+                continue;
+            };
+            let (key, other_key) = (format!("{path}{name}"), format!("{other_path}{name}"));
+            let (value, other_value) = tokio::try_join!(
+                Box::pin(self.secret_value(&key, format)),
+                Box::pin(other.secret_value(&other_key, other_format))
+            )?;
+            if Sha256::digest(&value) == Sha256::digest(&other_value) {
+                diff.unchanged.push(name);
+            } else {
+                diff.changed.push(name);
+            }
+        }
+        Ok(diff)
+    }
+
+    #[must_use]
+    /// Return true if both vaults store their objects in the same bucket or directory.
+    ///
+    /// The vaults may still use different prefixes.
+    pub fn shares_storage_with(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+            || self
+                .storage
+                .location()
+                .is_some_and(|location| other.storage.location() == Some(location))
+    }
+
+    /// Copy secret to the destination key name in the target vault.
+    ///
+    /// The target can be this same vault, or another vault using the same storage.
+    /// If both vaults use the same key, the encrypted objects are copied without decrypting,
+    /// otherwise the secret is decrypted and encrypted again for the target vault.
+    /// Metadata is copied as is.
+    /// Secrets using the deprecated encryption method are stored with AES-GCM in the destination.
+    /// Fails if the source and destination resolve to the same stored objects.
+    pub async fn copy(
+        &self,
+        source: &str,
+        target: &Self,
+        destination: &str,
+    ) -> Result<(), VaultError> {
+        let same_storage = self.shares_storage_with(target);
+        let source_keys = S3DataKeys::new(&self.full_key_name(source));
+        let destination_keys = S3DataKeys::new(&target.full_key_name(destination));
+        if same_storage && source_keys.key == destination_keys.key {
+            return Err(VaultError::SameKeyError(source.to_string()));
+        }
+
+        if !self.storage.object_exists(&source_keys.meta).await?
+            && self
+                .storage
+                .object_exists(&source_keys.legacy_cipher)
+                .await?
         {
-            Ok(_) => Ok(true),
-            Err(e) => {
-                let service_error = e.into_service_error();
-                if service_error.is_not_found() {
-                    // The object does not exist
-                    Ok(false)
-                } else {
-                    // Propagate other errors like networking or permissions
-                    Err(VaultError::S3HeadObjectError(service_error))
-                }
+            let value = self.secret_value(source, SecretFormat::Deprecated).await?;
+            return Box::pin(target.store(destination, &value)).await;
+        }
+
+        if same_storage {
+            // Data key is copied last since it marks the key as existing
+            for (from, to) in [
+                (&source_keys.cipher, &destination_keys.cipher),
+                (&source_keys.meta, &destination_keys.meta),
+                (&source_keys.key, &destination_keys.key),
+            ] {
+                self.storage.copy_object(from, to).await?;
             }
+            Ok(())
+        } else if self.keys.key_id().is_some() && self.keys.key_id() == target.keys.key_id() {
+            let (data_key, cipher_text, meta) = tokio::try_join!(
+                self.storage.get_object(&source_keys.key),
+                self.storage.get_object(&source_keys.cipher),
+                self.storage.get_object(&source_keys.meta),
+            )?;
+            tokio::try_join!(
+                target
+                    .storage
+                    .put_object(&destination_keys.cipher, cipher_text),
+                target.storage.put_object(&destination_keys.meta, meta),
+            )?;
+            target
+                .storage
+                .put_object(&destination_keys.key, data_key)
+                .await
+        } else {
+            let value = Box::pin(self.lookup(source)).await?;
+            let meta: Meta =
+                serde_json::from_slice(&self.storage.get_object(&source_keys.meta).await?)?;
+            let compression = meta.compression();
+            target
+                .write(destination, value.as_bytes(), meta.metadata, compression)
+                .await
         }
     }
 
-    /// Store encrypted data with given key name in S3
-    pub async fn store(&self, name: &str, data: &[u8]) -> Result<(), VaultError> {
-        let encrypted = self.encrypt(data).await?;
+    /// Move secret to the destination key name in the target vault.
+    ///
+    /// The copied secret is decrypted before deleting the source,
+    /// so a failure partway through never loses the secret.
+    pub async fn rename(
+        &self,
+        source: &str,
+        target: &Self,
+        destination: &str,
+    ) -> Result<(), VaultError> {
+        self.copy(source, target, destination).await?;
+        Box::pin(target.lookup(destination)).await?;
+        self.delete(source).await
+    }
+
+    /// Read all secrets under the given path with metadata into an archive.
+    ///
+    /// The path should be empty for all secrets, or end with `/`.
+    /// Secrets using the deprecated encryption method are included without metadata,
+    /// so they are stored with AES-GCM when the archive is imported.
+    /// Incomplete secrets are not included.
+    pub async fn export(&self, path: &str) -> Result<Archive, VaultError> {
+        let secrets = futures::stream::iter(self.formats_under(path).await?)
+            .map(|(name, format)| Box::pin(self.archived_secret(name, format)))
+            .buffered(Self::DEFAULT_CONCURRENCY)
+            .try_collect()
+            .await?;
+        Ok(Archive { secrets })
+    }
+
+    /// Decrypt a single secret with its metadata for an archive.
+    async fn archived_secret(
+        &self,
+        name: String,
+        format: SecretFormat,
+    ) -> Result<ArchivedSecret, VaultError> {
+        if format == SecretFormat::Deprecated {
+            let value = self.secret_value(&name, format).await?;
+            return Ok(ArchivedSecret {
+                name,
+                value,
+                metadata: SecretMetadata::default(),
+                compression: None,
+            });
+        }
+
+        let (value, meta) = tokio::try_join!(Box::pin(self.lookup(&name)), self.meta(&name))?;
+        Ok(ArchivedSecret {
+            name,
+            value: value.to_bytes(),
+            compression: meta.compression(),
+            metadata: meta.metadata,
+        })
+    }
+
+    /// Store secrets from an archive.
+    ///
+    /// Archived metadata is stored as is, including timestamps and the creator,
+    /// and values are compressed with the codec they were exported with.
+    /// Existing secrets are handled according to the given policy.
+    pub async fn import(
+        &self,
+        archive: Archive,
+        policy: ImportPolicy,
+    ) -> Result<ImportResult, VaultError> {
+        let imported: Vec<(String, bool)> = futures::stream::iter(archive.secrets)
+            .map(|secret| Box::pin(self.import_secret(secret, policy)))
+            .buffered(Self::DEFAULT_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        let mut result = ImportResult::default();
+        for (name, replaced) in imported {
+            if replaced {
+                result.imported.push(name);
+            } else {
+                result.skipped.push(name);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Store a single archived secret unless the policy keeps the existing one.
+    ///
+    /// Returns the key name and whether the secret was stored.
+    async fn import_secret(
+        &self,
+        secret: ArchivedSecret,
+        policy: ImportPolicy,
+    ) -> Result<(String, bool), VaultError> {
+        let replace = match policy {
+            ImportPolicy::Overwrite => true,
+            ImportPolicy::Skip => !self.exists(&secret.name).await?,
+            ImportPolicy::Merge => match self.metadata(&secret.name).await {
+                Ok(existing) => secret.metadata.updated_at() > existing.updated_at(),
+                Err(VaultError::KeyDoesNotExistError) => true,
+                Err(err) => return Err(err),
+            },
+        };
+        if replace {
+            self.write(
+                &secret.name,
+                &secret.value,
+                secret.metadata,
+                secret.compression,
+            )
+            .await?;
+        }
+        Ok((secret.name, replace))
+    }
+
+    #[must_use]
+    /// Get the key provider used for encrypting data keys.
+    pub fn key_provider(&self) -> &dyn KeyProvider {
+        self.keys.as_ref()
+    }
+
+    /// Encrypt data with the given metadata as is and write all objects for the key name.
+    async fn write(
+        &self,
+        name: &str,
+        data: &[u8],
+        metadata: SecretMetadata,
+        compression: Option<Compression>,
+    ) -> Result<(), VaultError> {
+        let encrypted = self.encrypt(data, metadata, compression).await?;
 
         let key = &self.full_key_name(name);
         let keys = S3DataKeys::new(key);
 
-        let put_cipher =
-            self.put_s3_object(keys.cipher, ByteStream::from(encrypted.aes_gcm_ciphertext));
-        let put_key = self.put_s3_object(keys.key, ByteStream::from(encrypted.data_key));
-        let put_meta = self.put_s3_object(keys.meta, ByteStream::from(encrypted.meta.into_bytes()));
+        let put_cipher = self
+            .storage
+            .put_object(&keys.cipher, encrypted.aes_gcm_ciphertext);
+        let put_key = self.storage.put_object(&keys.key, encrypted.data_key);
+        let put_meta = self
+            .storage
+            .put_object(&keys.meta, encrypted.meta.into_bytes());
 
         tokio::try_join!(put_cipher, put_key, put_meta)?;
 
         Ok(())
     }
 
-    /// Delete data in S3 for given key name.
+    /// Get secrets that have expired or expire within the given duration.
+    ///
+    /// Returns key names with expiry times, sorted by expiry.
+    pub async fn all_expiring(
+        &self,
+        within: Duration,
+    ) -> Result<Vec<(String, DateTime)>, VaultError> {
+        let limit = SystemTime::now()
+            .checked_add(within)
+            .map(DateTime::from)
+            .ok_or(VaultError::DurationOverflowError(within))?;
+        let mut expiring: Vec<(String, DateTime)> = futures::stream::iter(self.all().await?)
+            .map(|name| async move {
+                let expires = self.metadata(&name).await?.expires_at();
+                Ok::<_, VaultError>(
+                    expires
+                        .filter(|expires| *expires <= limit)
+                        .map(|expires| (name, expires)),
+                )
+            })
+            .buffered(Self::DEFAULT_CONCURRENCY)
+            .try_filter_map(|expiring| async move { Ok(expiring) })
+            .try_collect()
+            .await?;
+        expiring.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        Ok(expiring)
+    }
+
+    /// Get an identifier for the currently stored version of the given key.
+    ///
+    /// Uses the S3 entity tag of the encrypted value,
+    /// or the modification time and size when the storage has no entity tags.
+    /// Storing the key again always changes the version, even with the same value.
+    pub async fn version(&self, name: &str) -> Result<String, VaultError> {
+        let cipher = S3DataKeys::new(&self.full_key_name(name)).cipher;
+        let object = self
+            .storage
+            .list_objects(&cipher)
+            .await?
+            .into_iter()
+            .find(|object| object.key == cipher)
+            .ok_or(VaultError::KeyDoesNotExistError)?;
+        Ok(object.etag.unwrap_or_else(|| {
+            format!(
+                "{}-{}",
+                object.last_modified.map_or(0, |time| time.as_nanos()),
+                object.size
+            )
+        }))
+    }
+
+    /// Get user metadata for the given key name.
+    ///
+    /// Only the meta object is read, so the metadata is not authenticated
+    /// until the secret itself is decrypted.
+    pub async fn metadata(&self, name: &str) -> Result<SecretMetadata, VaultError> {
+        Ok(self.meta(name).await?.metadata)
+    }
+
+    /// Read the stored meta object for the key name.
+    async fn meta(&self, name: &str) -> Result<Meta, VaultError> {
+        let keys = S3DataKeys::new(&self.full_key_name(name));
+        Ok(serde_json::from_slice(
+            &self.storage.get_object(&keys.meta).await?,
+        )?)
+    }
+
+    /// Delete data in storage for given key name.
     pub async fn delete(&self, name: &str) -> Result<(), VaultError> {
         if !self.exists(name).await? {
             return Err(VaultError::S3DeleteObjectKeyMissingError {
@@ -332,23 +963,143 @@ impl Vault {
         }
 
         let key = &self.full_key_name(name);
-        let identifiers = S3DataKeys::new(key).to_object_identifiers()?;
-        self.s3
-            .delete_objects()
-            .bucket(&self.cloudformation_params.bucket_name)
-            .delete(Delete::builder().set_objects(Some(identifiers)).build()?)
-            .send()
-            .await?;
-
-        Ok(())
+        self.storage
+            .delete_objects(&S3DataKeys::new(key).to_vec())
+            .await
     }
 
     /// Delete data for multiple keys.
+    ///
+    /// Deletes as many of the keys as possible like [`Self::delete_many_with_results`],
+    /// and returns an error listing all the keys that could not be deleted.
     pub async fn delete_many(&self, names: &[String]) -> Result<(), VaultError> {
-        for name in names {
-            self.delete(name).await?;
+        let failed: Vec<String> = self
+            .delete_many_with_results(names, Self::DEFAULT_CONCURRENCY)
+            .await?
+            .into_iter()
+            .filter_map(|(name, result)| result.err().map(|error| format!("'{name}': {error}")))
+            .collect();
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(VaultError::DeleteManyError(failed))
         }
-        Ok(())
+    }
+
+    /// Delete data for multiple keys and return the result for each key.
+    ///
+    /// Existing keys are found with a single listing,
+    /// and the objects are deleted in batches with at most `concurrency` requests at a time.
+    /// All objects of one key are deleted in the same batch,
+    /// so a failed batch does not leave keys partially deleted.
+    /// A failure for one key does not stop deleting the others.
+    /// Duplicate names are deleted and returned only once.
+    /// Returns an error only if listing the existing keys fails.
+    pub async fn delete_many_with_results(
+        &self,
+        names: &[String],
+        concurrency: usize,
+    ) -> Result<BulkResult<()>, VaultError> {
+        let mut unique = HashSet::new();
+        let names: Vec<&String> = names.iter().filter(|name| unique.insert(*name)).collect();
+        let full_names: Vec<String> = names.iter().map(|name| self.full_key_name(name)).collect();
+        let prefix = common_prefix(&full_names);
+        let existing: HashSet<String> = self
+            .storage
+            .list_objects(prefix)
+            .await?
+            .into_iter()
+            .map(|object| object.key)
+            .collect();
+
+        let mut owners: HashMap<String, usize> = HashMap::new();
+        let mut batches: Vec<Vec<String>> = Vec::new();
+        for (index, name) in full_names.iter().enumerate() {
+            let keys = S3DataKeys::new(name);
+            if !existing.contains(&keys.key) {
+                continue;
+            }
+            let objects = keys.to_vec();
+            if !batches
+                .last()
+                .is_some_and(|batch| batch.len() + objects.len() <= MAX_DELETE_OBJECTS)
+            {
+                batches.push(Vec::new());
+            }
+            for key in &objects {
+                owners.insert(key.clone(), index);
+            }
+            if let Some(batch) = batches.last_mut() {
+                batch.extend(objects);
+            }
+        }
+
+        let mut failures: HashMap<usize, VaultError> = HashMap::new();
+        let mut results = futures::stream::iter(&batches)
+            .map(|batch| async move { (batch, self.storage.try_delete_objects(batch).await) })
+            .buffer_unordered(concurrency.max(1));
+        while let Some((batch, result)) = results.next().await {
+            match result {
+                Ok(failed) => {
+                    for (key, error) in failed {
+                        if let Some(&index) = owners.get(&key) {
+                            failures.entry(index).or_insert(error);
+                        }
+                    }
+                }
+                Err(error) => {
+                    for key in batch {
+                        failures.entry(owners[key]).or_insert_with(|| {
+                            VaultError::S3DeleteObjectFailedError(key.clone(), error.to_string())
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(names
+            .into_iter()
+            .zip(&full_names)
+            .enumerate()
+            .map(|(index, (name, full_name))| {
+                let result = if !existing.contains(&S3DataKeys::new(full_name).key) {
+                    Err(VaultError::S3DeleteObjectKeyMissingError { name: name.clone() })
+                } else if let Some(error) = failures.remove(&index) {
+                    Err(error)
+                } else {
+                    Ok(())
+                };
+                (name.clone(), result)
+            })
+            .collect())
+    }
+
+    /// Return values for multiple keys.
+    ///
+    /// Looks up at most `concurrency` keys at a time.
+    /// A failure for one key does not stop looking up the others.
+    pub async fn lookup_many(&self, names: &[String], concurrency: usize) -> BulkResult<Value> {
+        futures::stream::iter(names)
+            .map(|name| async move { (name.clone(), Box::pin(self.lookup(name)).await) })
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Store values for multiple keys.
+    ///
+    /// Stores at most `concurrency` keys at a time, keeping existing metadata like `store`.
+    /// A failure for one key does not stop storing the others.
+    pub async fn store_many(
+        &self,
+        values: &[(String, Vec<u8>)],
+        concurrency: usize,
+    ) -> BulkResult<()> {
+        futures::stream::iter(values)
+            .map(|(name, data)| async move { (name.clone(), Box::pin(self.store(name, data)).await) })
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
     }
 
     /// Return value for the given key name.
@@ -358,10 +1109,10 @@ impl Vault {
         let key = &self.full_key_name(name);
         let keys = S3DataKeys::new(key);
 
-        let data_key = self.get_s3_object(keys.key).await?;
+        let data_key = self.storage.get_object(&keys.key).await?;
 
-        let cipher_text = self.get_s3_object(keys.cipher);
-        let meta_add = self.get_s3_object(keys.meta);
+        let cipher_text = self.storage.get_object(&keys.cipher);
+        let meta_add = self.storage.get_object(&keys.meta);
 
         match tokio::try_join!(cipher_text, meta_add) {
             Ok((cipher_text, meta_add)) => {
@@ -380,123 +1131,231 @@ impl Vault {
         }
     }
 
-    async fn lookup_aesgcm_data(
+    /// Decrypt the value for the given key name and write it to the writer.
+    ///
+    /// Values stored with [`Self::store_stream`] are decrypted segment by segment as they are
+    /// downloaded, so they are never fully held in memory.
+    /// Only authenticated data is written,
+    /// but the writer may contain part of the value if decryption fails.
+    /// Returns the metadata of the secret.
+    pub async fn lookup_to_writer(
         &self,
-        data_key: &[u8],
-        cipher_text: &Vec<u8>,
-        meta_add: &Vec<u8>,
-    ) -> Result<Value, VaultError> {
-        let meta: Meta = serde_json::from_slice(meta_add)?;
-        let cipher: AesGcm<Aes256, U12> =
-            AesGcm::new_from_slice(self.direct_decrypt(data_key).await?.as_slice())?;
-        let nonce = base64::engine::general_purpose::STANDARD.decode(meta.nonce)?;
-        let nonce = Nonce::from_slice(nonce.as_slice());
-        let decrypted_bytes = cipher
-            .decrypt(
-                nonce,
-                Payload {
-                    msg: cipher_text,
-                    aad: meta_add,
-                },
-            )
-            .map_err(|_| VaultError::NonceDecryptError)?;
+        name: &str,
+        writer: &mut (impl AsyncWrite + Unpin + Send),
+    ) -> Result<SecretMetadata, VaultError> {
+        let key = &self.full_key_name(name);
+        let keys = S3DataKeys::new(key);
 
-        match String::from_utf8(decrypted_bytes) {
-            Ok(valid_string) => Ok(Value::Utf8(valid_string)),
-            Err(from_utf8_error) => Ok(Value::Binary(from_utf8_error.into_bytes())),
-        }
+        let meta_add = match self.storage.get_object(&keys.meta).await {
+            Ok(meta_add) => meta_add,
+            Err(VaultError::KeyDoesNotExistError) => {
+                // Data key exists but the meta object does not:
+                // This secret has been encrypted with the old deprecated method
+                return Err(if self.storage.object_exists(&keys.key).await? {
+                    VaultError::DeprecatedEncryptionError
+                } else {
+                    VaultError::KeyDoesNotExistError
+                });
+            }
+            Err(err) => return Err(err),
+        };
+        let meta: Meta = serde_json::from_slice(&meta_add)?;
+        if meta.alg != STREAM_ALGORITHM {
+            let (data_key, cipher_text) = tokio::try_join!(
+                self.storage.get_object(&keys.key),
+                self.storage.get_object(&keys.cipher)
+            )?;
+            let value = self
+                .lookup_aesgcm_data(&data_key, &cipher_text, &meta_add)
+                .await?;
+            writer
+                .write_all(value.as_bytes())
+                .await
+                .map_err(VaultError::StreamWriteError)?;
+            writer.flush().await.map_err(VaultError::StreamWriteError)?;
+            return Ok(meta.metadata);
+        }
+
+        let (data_key, mut cipher_text) = tokio::try_join!(
+            self.storage.get_object(&keys.key),
+            self.storage.get_object_stream(&keys.cipher)
+        )?;
+        let data_key = self.keys.decrypt_data_key(&data_key).await?;
+        let nonce = base64::engine::general_purpose::STANDARD.decode(&meta.nonce)?;
+        let mut decryptor = SegmentDecryptor::new(&data_key, &nonce, meta.segment_size, &meta_add)?;
+
+        while let Some(chunk) = cipher_text.try_next().await? {
+            let plaintext = decryptor.update(&chunk)?;
+            writer
+                .write_all(&plaintext)
+                .await
+                .map_err(VaultError::StreamWriteError)?;
+        }
+        writer
+            .write_all(&decryptor.finish()?)
+            .await
+            .map_err(VaultError::StreamWriteError)?;
+        writer.flush().await.map_err(VaultError::StreamWriteError)?;
+        Ok(meta.metadata)
     }
 
-    /// Decrypt data with KMS.
-    pub async fn direct_decrypt(&self, encrypted_data: &[u8]) -> Result<Vec<u8>, VaultError> {
-        self.kms
-            .decrypt()
-            .ciphertext_blob(Blob::new(encrypted_data))
-            .send()
-            .await?
-            .plaintext()
-            .map(|blob| blob.to_owned().into_inner())
-            .ok_or(VaultError::KmsDataKeyPlainTextMissingError)
+    /// Re-encrypt a secret stored with the old deprecated encryption method using AES-GCM.
+    ///
+    /// Returns `true` if the secret was re-encrypted,
+    /// or `false` if it already uses AES-GCM and nothing needed to be done.
+    pub async fn recrypt(&self, name: &str) -> Result<bool, VaultError> {
+        let key = &self.full_key_name(name);
+        let keys = S3DataKeys::new(key);
+
+        let (has_cipher, has_meta) = tokio::try_join!(
+            self.storage.object_exists(&keys.cipher),
+            self.storage.object_exists(&keys.meta)
+        )?;
+        if has_cipher && has_meta {
+            return Ok(false);
+        }
+
+        let (data_key, cipher_text) = tokio::try_join!(
+            self.storage.get_object(&keys.key),
+            self.storage.get_object(&keys.legacy_cipher)
+        )?;
+        let data = self.decrypt_legacy_data(&data_key, cipher_text).await?;
+
+        Box::pin(self.store(name, &data)).await?;
+
+        // The legacy ciphertext can't be decrypted with the new data key anymore
+        self.storage.delete_objects(&[keys.legacy_cipher]).await?;
+
+        Ok(true)
     }
 
-    /// Encrypt data with KMS.
-    pub async fn direct_encrypt(&self, data: &[u8]) -> Result<Vec<u8>, VaultError> {
-        let key = self
-            .cloudformation_params
-            .key_arn
-            .as_ref()
-            .ok_or(VaultError::KeyArnMissingError)?;
-
-        let response = self
-            .kms
-            .encrypt()
-            .key_id(key)
-            .plaintext(Blob::new(data))
-            .send()
-            .await
-            .map_err(VaultError::from)?;
+    /// Get all secrets that still use the old deprecated encryption method.
+    ///
+    /// Returns a list of key names.
+    pub async fn all_deprecated(&self) -> Result<Vec<String>, VaultError> {
+        Ok(self
+            .all_info()
+            .await?
+            .into_iter()
+            .filter(|info| info.format == SecretFormat::Deprecated)
+            .map(|info| info.name)
+            .collect())
+    }
 
-        let ciphertext = response
-            .ciphertext_blob
-            .ok_or(VaultError::CiphertextEncryptionError)?
-            .into_inner();
+    /// Re-encrypt the data key of a secret under the given destination master key.
+    ///
+    /// Only the `.key` object is rewritten, the encrypted value is not touched.
+    /// Returns `true` if the data key was rewrapped,
+    /// or `false` if it was already encrypted under the destination key.
+    pub async fn rewrap(&self, name: &str, destination_key: &str) -> Result<bool, VaultError> {
+        let key = &self.full_key_name(name);
+        let keys = S3DataKeys::new(key);
 
-        Ok(ciphertext)
+        let data_key = self.storage.get_object(&keys.key).await?;
+        match self.keys.reencrypt(&data_key, destination_key).await? {
+            Some(new_data_key) => {
+                self.storage.put_object(&keys.key, new_data_key).await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
     }
 
-    /// Get S3 Object data for given key as a vec of bytes.
-    async fn get_s3_object(&self, key: String) -> Result<Vec<u8>, VaultError> {
-        let response = self
-            .s3
-            .get_object()
-            .bucket(self.cloudformation_params.bucket_name.clone())
-            .key(&key)
-            .send()
-            .await
-            .map_err(|err| {
-                if let Some(service_error) = err.as_service_error() {
-                    if service_error.is_no_such_key() {
-                        VaultError::KeyDoesNotExistError
-                    } else {
-                        VaultError::S3GetObjectError(err)
-                    }
-                } else {
-                    VaultError::S3GetObjectError(err)
-                }
-            })?;
+    async fn lookup_aesgcm_data(
+        &self,
+        data_key: &[u8],
+        cipher_text: &Vec<u8>,
+        meta_add: &Vec<u8>,
+    ) -> Result<Value, VaultError> {
+        let meta: Meta = serde_json::from_slice(meta_add)?;
+        let data_key = self.keys.decrypt_data_key(data_key).await?;
+        let nonce = base64::engine::general_purpose::STANDARD.decode(&meta.nonce)?;
+        let decrypted_bytes = match meta.alg.as_str() {
+            "AESGCM" | ZSTD_ALGORITHM => {
+                let nonce = meta.compression().map_or_else(
+                    || *Nonce::from_slice(nonce.as_slice()),
+                    |codec| Self::compressed_value_nonce(codec, &nonce),
+                );
+                let cipher: AesGcm<Aes256, U12> = AesGcm::new_from_slice(data_key.as_slice())?;
+                cipher
+                    .decrypt(
+                        &nonce,
+                        Payload {
+                            msg: cipher_text,
+                            aad: meta_add,
+                        },
+                    )
+                    .map_err(|_| VaultError::NonceDecryptError)?
+            }
+            STREAM_ALGORITHM => {
+                let mut decryptor =
+                    SegmentDecryptor::new(&data_key, &nonce, meta.segment_size, meta_add)?;
+                let mut decrypted_bytes = decryptor.update(cipher_text)?;
+                decrypted_bytes.extend(decryptor.finish()?);
+                decrypted_bytes
+            }
+            alg => return Err(VaultError::UnsupportedAlgorithmError(alg.to_owned())),
+        };
+        let decrypted_bytes = match meta.compression() {
+            Some(codec) => codec.decompress(&decrypted_bytes)?,
+            None => decrypted_bytes,
+        };
 
-        let body = response
-            .body
-            .collect()
-            .await
-            .map_err(|_| VaultError::S3GetObjectBodyError)?;
+        match String::from_utf8(decrypted_bytes) {
+            Ok(valid_string) => Ok(Value::Utf8(valid_string)),
+            Err(from_utf8_error) => Ok(Value::Binary(from_utf8_error.into_bytes())),
+        }
+    }
 
-        Ok(body.to_vec())
+    /// Decrypt data stored with the old deprecated AES-CTR encryption method.
+    async fn decrypt_legacy_data(
+        &self,
+        data_key: &[u8],
+        mut cipher_text: Vec<u8>,
+    ) -> Result<Vec<u8>, VaultError> {
+        let data_key = self.keys.decrypt_data_key(data_key).await?;
+        let mut cipher = LegacyCipher::new_from_slices(&data_key, &LEGACY_STATIC_IV)?;
+        cipher.apply_keystream(&mut cipher_text);
+        Ok(cipher_text)
     }
 
-    /// Encrypt data
-    async fn encrypt(&self, data: &[u8]) -> Result<EncryptObject, VaultError> {
-        let key = self
-            .cloudformation_params
-            .key_arn
-            .clone()
-            .ok_or(VaultError::KeyArnMissingError)?;
+    /// Decrypt data with the master key, usually KMS.
+    pub async fn direct_decrypt(&self, encrypted_data: &[u8]) -> Result<Vec<u8>, VaultError> {
+        self.keys.decrypt(encrypted_data).await
+    }
 
-        let key_dict = self
-            .kms
-            .generate_data_key()
-            .key_id(key)
-            .key_spec(DataKeySpec::Aes256)
-            .send()
-            .await?;
+    /// Encrypt data with the master key, usually KMS.
+    pub async fn direct_encrypt(&self, data: &[u8]) -> Result<Vec<u8>, VaultError> {
+        self.keys.encrypt(data).await
+    }
 
-        let plaintext = key_dict
-            .plaintext()
-            .ok_or(VaultError::KmsDataKeyPlainTextMissingError)?;
+    /// Encrypt data, compressing it first if a codec is given and it makes the data smaller.
+    async fn encrypt(
+        &self,
+        data: &[u8],
+        metadata: SecretMetadata,
+        compression: Option<Compression>,
+    ) -> Result<EncryptObject, VaultError> {
+        let compressed = compression
+            .map(|codec| codec.compress(data).map(|compressed| (codec, compressed)))
+            .transpose()?
+            .filter(|(_, compressed)| compressed.len() < data.len());
+        let (compression, data) = match &compressed {
+            Some((codec, compressed)) => (Some(*codec), compressed.as_slice()),
+            None => (None, data),
+        };
+
+        let data_key = self.keys.generate_data_key_for(data.len()).await?;
 
-        let aesgcm_cipher: AesGcm<Aes256, U12> = AesGcm::new_from_slice(plaintext.as_ref())?;
+        let aesgcm_cipher: AesGcm<Aes256, U12> =
+            AesGcm::new_from_slice(data_key.plaintext.as_slice())?;
         let nonce = Self::create_random_nonce();
-        let meta = Meta::aesgcm(&nonce).to_json()?;
+        let meta = match compression {
+            Some(codec) => Meta::aesgcm_compressed(&nonce, codec, metadata),
+            None => Meta::aesgcm(&nonce, metadata),
+        }
+        .to_json()?;
+        let nonce = compression.map_or(nonce, |codec| Self::compressed_value_nonce(codec, &nonce));
         let aes_gcm_ciphertext = aesgcm_cipher
             .encrypt(
                 &nonce,
@@ -507,34 +1366,84 @@ impl Vault {
             )
             .map_err(|_| VaultError::CiphertextEncryptionError)?;
 
-        let data_key = key_dict
-            .ciphertext_blob()
-            .ok_or(VaultError::CiphertextEncryptionError)?
-            .to_owned()
-            .into_inner();
-
         Ok(EncryptObject {
-            data_key,
+            data_key: data_key.ciphertext,
             aes_gcm_ciphertext,
             meta,
         })
     }
 
-    /// Send PUT request with the given byte data.
-    async fn put_s3_object(
+    /// Get the storage format of all complete secrets under the given path, without the prefix.
+    async fn formats_under(
         &self,
-        key: String,
-        body: ByteStream,
-    ) -> Result<PutObjectOutput, VaultError> {
+        path: &str,
+    ) -> Result<BTreeMap<String, SecretFormat>, VaultError> {
+        let mut objects = BTreeMap::new();
+        for object in self.list_objects(path).await? {
+            SecretObjects::insert(&mut objects, object);
+        }
+        Ok(objects
+            .into_iter()
+            .map(|(name, objects)| (name, objects.format()))
+            .filter(|(_, format)| *format != SecretFormat::Incomplete)
+            .collect())
+    }
+
+    /// Decrypt the value of a secret stored in the given format.
+    async fn secret_value(&self, name: &str, format: SecretFormat) -> Result<Vec<u8>, VaultError> {
+        if format != SecretFormat::Deprecated {
+            return Ok(Box::pin(self.lookup(name)).await?.to_bytes());
+        }
+        let keys = S3DataKeys::new(&self.full_key_name(name));
+        let (data_key, cipher_text) = tokio::try_join!(
+            self.storage.get_object(&keys.key),
+            self.storage.get_object(&keys.legacy_cipher)
+        )?;
+        self.decrypt_legacy_data(&data_key, cipher_text).await
+    }
+
+    /// Get names of all secrets under the given path, without the prefix.
+    async fn names_under(&self, path: &str) -> Result<Vec<String>, VaultError> {
         Ok(self
-            .s3
-            .put_object()
-            .bucket(&self.cloudformation_params.bucket_name)
-            .key(key)
-            .acl(aws_sdk_s3::types::ObjectCannedAcl::Private)
-            .body(body)
-            .send()
-            .await?)
+            .list_objects(path)
+            .await?
+            .iter()
+            .filter_map(|object| object.key.strip_suffix(".aesgcm.encrypted"))
+            .map(ToOwned::to_owned)
+            .collect())
+    }
+
+    /// Get the IAM ARN of the current AWS identity, only looked up once.
+    ///
+    /// The creator is only informational,
+    /// so a failed lookup leaves it empty instead of failing the store.
+    async fn caller_arn(&self) -> Option<String> {
+        let sts = self.sts.as_ref()?;
+        self.caller_arn
+            .get_or_init(|| async {
+                sts.get_caller_identity()
+                    .send()
+                    .await
+                    .ok()
+                    .and_then(|output| output.arn)
+            })
+            .await
+            .clone()
+    }
+
+    /// List objects under the given path and the configured prefix,
+    /// with the prefix removed from the keys.
+    async fn list_objects(&self, path: &str) -> Result<Vec<ObjectInfo>, VaultError> {
+        Ok(self
+            .storage
+            .list_objects(&self.full_key_name(path))
+            .await?
+            .into_iter()
+            .filter_map(|mut object| {
+                object.key = object.key.strip_prefix(&self.prefix)?.to_owned();
+                Some(object)
+            })
+            .collect())
     }
 
     /// Add prefix to key if prefix has been specified.
@@ -553,6 +1462,18 @@ impl Vault {
         rng.fill(&mut nonce);
         Nonce::from(nonce)
     }
+
+    /// Derive the nonce for encrypting a compressed value from the nonce stored in the meta object.
+    ///
+    /// Clients that don't check the algorithm decrypt with the stored nonce,
+    /// so they fail to authenticate compressed values instead of returning the compressed bytes.
+    fn compressed_value_nonce(codec: Compression, nonce: &[u8]) -> Nonce<U12> {
+        let digest = Sha256::new()
+            .chain_update(codec.algorithm())
+            .chain_update(nonce)
+            .finalize();
+        Nonce::clone_from_slice(&digest[..12])
+    }
 }
 
 impl fmt::Display for Vault {
@@ -564,3 +1485,1080 @@ impl fmt::Display for Vault {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::path::Path;
+    use std::time::{Duration, SystemTime};
+
+    use aes_gcm::aead::consts::U12;
+    use aes_gcm::aead::{Aead, Payload};
+    use aes_gcm::aes::Aes256;
+    use aes_gcm::{AesGcm, KeyInit, Nonce};
+    use aws_sdk_s3::primitives::DateTime;
+    use base64::Engine;
+    use ctr::cipher::{KeyIvInit, StreamCipher};
+    use tempfile::TempDir;
+
+    use super::{common_prefix, LegacyCipher, S3DataKeys, Vault, LEGACY_STATIC_IV};
+    use crate::archive::{Archive, ImportPolicy};
+    use crate::compression::{Compression, ZSTD_ALGORITHM};
+    use crate::errors::VaultError;
+    use crate::key_provider::{DataKeyCacheLimits, LocalKeyProvider};
+    use crate::{
+        DirectoryCount, KeyMatcher, Meta, SecretFormat, SecretMetadata, Value, VaultConfig,
+    };
+
+    /// Create an offline vault using local storage and a local master key.
+    ///
+    /// The test directory is removed when the returned guard is dropped.
+    async fn local_vault() -> (Vault, TempDir) {
+        let root = tempfile::Builder::new()
+            .prefix("nitor-vault-test-")
+            .tempdir()
+            .expect("failed to create test dir");
+        std::fs::write(
+            root.path().join("master.key"),
+            LocalKeyProvider::generate().to_base64(),
+        )
+        .expect("failed to write key file");
+
+        let vault = local_vault_with_prefix(root.path(), None).await;
+        (vault, root)
+    }
+
+    /// Create another offline vault using the same test directory as `local_vault`.
+    async fn local_vault_with_prefix(root: &Path, prefix: Option<&str>) -> Vault {
+        Vault::from_config(VaultConfig {
+            region: Some("eu-west-1".to_string()),
+            prefix: prefix.map(ToOwned::to_owned),
+            local_dir: Some(root.join("data").display().to_string()),
+            local_key: Some(root.join("master.key").display().to_string()),
+            ..Default::default()
+        })
+        .await
+        .expect("failed to create local vault")
+    }
+
+    #[tokio::test]
+    async fn local_store_lookup_and_delete() {
+        let (vault, _root) = local_vault().await;
+
+        vault
+            .store("team/secret", b"value")
+            .await
+            .expect("store failed");
+        assert!(vault.exists("team/secret").await.expect("exists failed"));
+        assert_eq!(vault.all().await.expect("all failed"), vec!["team/secret"]);
+
+        let value = vault.lookup("team/secret").await.expect("lookup failed");
+        assert!(matches!(value, Value::Utf8(ref text) if text == "value"));
+
+        vault.delete("team/secret").await.expect("delete failed");
+        assert!(!vault.exists("team/secret").await.expect("exists failed"));
+        assert!(matches!(
+            vault.lookup("team/secret").await,
+            Err(VaultError::KeyDoesNotExistError)
+        ));
+    }
+
+    #[tokio::test]
+    async fn all_with_prefix() {
+        let (vault, root) = local_vault().await;
+        let team_vault = local_vault_with_prefix(root.path(), Some("team")).await;
+
+        vault.store("other", b"value").await.expect("store failed");
+        vault
+            .store("team/first", b"value")
+            .await
+            .expect("store failed");
+        team_vault
+            .store("second", b"value")
+            .await
+            .expect("store failed");
+
+        assert_eq!(
+            vault.all().await.expect("all failed"),
+            vec!["other", "team/first", "team/second"]
+        );
+        assert_eq!(
+            team_vault.all().await.expect("all failed"),
+            vec!["first", "second"]
+        );
+    }
+
+    #[tokio::test]
+    async fn all_info_formats() {
+        let (vault, _root) = local_vault().await;
+
+        vault
+            .store("current", b"value")
+            .await
+            .expect("store failed");
+        for key in ["old.key", "old.encrypted", "partial.key"] {
+            vault
+                .storage
+                .put_object(key, b"data".to_vec())
+                .await
+                .expect("put failed");
+        }
+
+        let formats: Vec<(String, SecretFormat)> = vault
+            .all_info()
+            .await
+            .expect("all_info failed")
+            .into_iter()
+            .map(|info| (info.name, info.format))
+            .collect();
+        assert_eq!(
+            formats,
+            vec![
+                ("current".to_string(), SecretFormat::AesGcm),
+                ("old".to_string(), SecretFormat::Deprecated),
+                ("partial".to_string(), SecretFormat::Incomplete),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn store_with_metadata_and_overwrite() {
+        let (vault, _root) = local_vault().await;
+        let metadata = SecretMetadata {
+            description: Some("Database password".to_string()),
+            tags: BTreeMap::from([("env".to_string(), "prod".to_string())]),
+            ..SecretMetadata::default()
+        };
+        vault
+            .store_with_metadata("db", b"first", metadata)
+            .await
+            .expect("store failed");
+
+        let stored = vault.metadata("db").await.expect("metadata failed");
+        assert_eq!(stored.description.as_deref(), Some("Database password"));
+        assert!(stored.created.is_some());
+        assert_eq!(stored.created, stored.updated);
+        // Offline vault has no AWS identity
+        assert!(stored.created_by.is_none());
+
+        let update = SecretMetadata {
+            owner: Some("backend".to_string()),
+            tags: BTreeMap::from([("team".to_string(), "core".to_string())]),
+            ..SecretMetadata::default()
+        };
+        vault
+            .store_with_metadata("db", b"second", update)
+            .await
+            .expect("store failed");
+
+        let updated = vault.metadata("db").await.expect("metadata failed");
+        assert_eq!(updated.description, stored.description);
+        assert_eq!(updated.owner.as_deref(), Some("backend"));
+        assert_eq!(updated.created, stored.created);
+        assert!(updated.has_tags(&BTreeMap::from([
+            ("env".to_string(), "prod".to_string()),
+            ("team".to_string(), "core".to_string()),
+        ])));
+        assert_eq!(
+            vault.lookup("db").await.expect("lookup failed").to_string(),
+            "second"
+        );
+    }
+
+    #[tokio::test]
+    async fn store_new_sets_creation_metadata() {
+        let (vault, _root) = local_vault().await;
+        let metadata = SecretMetadata {
+            owner: Some("backend".to_string()),
+            ..SecretMetadata::default()
+        };
+        vault
+            .store_new("db", b"value", metadata, Some(Compression::Zstd))
+            .await
+            .expect("store failed");
+
+        let stored = vault.metadata("db").await.expect("metadata failed");
+        assert_eq!(stored.owner.as_deref(), Some("backend"));
+        assert!(stored.created.is_some());
+        assert_eq!(stored.created, stored.updated);
+        assert_eq!(
+            vault.lookup("db").await.expect("lookup failed").as_bytes(),
+            b"value"
+        );
+    }
+
+    #[tokio::test]
+    async fn all_expiring() {
+        let (vault, _root) = local_vault().await;
+        let now = DateTime::from(SystemTime::now());
+        let expiry = |secs: i64| {
+            SecretMetadata::default().with_expiry(DateTime::from_secs(now.secs() + secs))
+        };
+        vault
+            .store_with_metadata("expired", b"value", expiry(-60))
+            .await
+            .expect("store failed");
+        vault
+            .store_with_metadata("soon", b"value", expiry(3600))
+            .await
+            .expect("store failed");
+        vault
+            .store_with_metadata("later", b"value", expiry(30 * 24 * 3600))
+            .await
+            .expect("store failed");
+        vault.store("never", b"value").await.expect("store failed");
+
+        let names = |expiring: Vec<(String, DateTime)>| {
+            expiring
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(
+                vault
+                    .all_expiring(Duration::ZERO)
+                    .await
+                    .expect("list failed")
+            ),
+            vec!["expired"]
+        );
+        assert_eq!(
+            names(
+                vault
+                    .all_expiring(Duration::from_secs(24 * 3600))
+                    .await
+                    .expect("list failed")
+            ),
+            vec!["expired", "soon"]
+        );
+        assert!(vault
+            .metadata("expired")
+            .await
+            .expect("metadata failed")
+            .is_expired());
+    }
+
+    #[tokio::test]
+    async fn export_and_import_policies() {
+        let (source, _source_root) = local_vault().await;
+        let (target, _target_root) = local_vault().await;
+        source.store("prod/a", b"new").await.expect("store failed");
+        source.store("prod/b", b"new").await.expect("store failed");
+        source.store("dev/c", b"new").await.expect("store failed");
+
+        let archive = source.export("prod/").await.expect("export failed");
+        assert_eq!(archive.secrets.len(), 2);
+
+        // Deprecated secrets are exported and stored with AES-GCM on import
+        store_legacy(&source, "legacy/old", b"legacy value").await;
+        let legacy = source.export("legacy/").await.expect("export failed");
+        assert_eq!(legacy.secrets.len(), 1);
+        assert_eq!(legacy.secrets[0].value, b"legacy value");
+        target
+            .import(legacy, ImportPolicy::Skip)
+            .await
+            .expect("import failed");
+        assert_eq!(
+            target
+                .lookup("legacy/old")
+                .await
+                .expect("lookup failed")
+                .to_string(),
+            "legacy value"
+        );
+
+        // Older existing value is kept with skip and replaced with merge
+        let mut older = archive.secrets[0].clone();
+        older.value = b"old".to_vec();
+        older.metadata.updated = Some("2020-01-01T00:00:00Z".to_string());
+        target
+            .import(
+                Archive {
+                    secrets: vec![older],
+                },
+                ImportPolicy::Overwrite,
+            )
+            .await
+            .expect("import failed");
+
+        let result = target
+            .import(archive.clone(), ImportPolicy::Skip)
+            .await
+            .expect("import failed");
+        assert_eq!(result.imported, vec!["prod/b"]);
+        assert_eq!(result.skipped, vec!["prod/a"]);
+        assert_eq!(
+            target
+                .lookup("prod/a")
+                .await
+                .expect("lookup failed")
+                .to_string(),
+            "old"
+        );
+
+        let result = target
+            .import(archive.clone(), ImportPolicy::Merge)
+            .await
+            .expect("import failed");
+        assert_eq!(result.imported, vec!["prod/a"]);
+        assert_eq!(
+            target
+                .lookup("prod/a")
+                .await
+                .expect("lookup failed")
+                .to_string(),
+            "new"
+        );
+        assert_eq!(
+            target.metadata("prod/a").await.expect("metadata failed"),
+            archive.secrets[0].metadata
+        );
+        assert_eq!(
+            target.all().await.expect("all failed"),
+            vec!["legacy/old", "prod/a", "prod/b"]
+        );
+    }
+
+    #[tokio::test]
+    async fn export_and_import_compressed() {
+        let (source, _source_root) = local_vault().await;
+        let (target, _target_root) = local_vault().await;
+        source
+            .store_compressed(
+                "zip/config",
+                "A".repeat(4000).as_bytes(),
+                SecretMetadata::default(),
+                Compression::Zstd,
+            )
+            .await
+            .expect("store failed");
+        let compressed = source.export("zip/").await.expect("export failed");
+        assert_eq!(compressed.secrets[0].compression, Some(Compression::Zstd));
+        target
+            .import(compressed, ImportPolicy::Skip)
+            .await
+            .expect("import failed");
+        assert_eq!(
+            target
+                .meta("zip/config")
+                .await
+                .expect("meta failed")
+                .compression(),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(
+            target
+                .lookup("zip/config")
+                .await
+                .expect("lookup failed")
+                .to_string(),
+            "A".repeat(4000)
+        );
+    }
+
+    #[tokio::test]
+    async fn copy_and_rename() {
+        let (vault, root) = local_vault().await;
+        let (other, _other_root) = local_vault().await;
+        let metadata = SecretMetadata {
+            description: Some("copied".to_string()),
+            ..SecretMetadata::default()
+        };
+        vault
+            .store_with_metadata("a", b"value", metadata)
+            .await
+            .expect("store failed");
+
+        vault
+            .copy("a", &vault, "team/b")
+            .await
+            .expect("copy failed");
+        assert_eq!(
+            vault
+                .lookup("team/b")
+                .await
+                .expect("lookup failed")
+                .to_string(),
+            "value"
+        );
+        assert!(matches!(
+            vault.copy("a", &vault, "a").await,
+            Err(VaultError::SameKeyError(_))
+        ));
+
+        // Separately created vault for the same storage is not used to overwrite the source
+        let same = local_vault_with_prefix(root.path(), None).await;
+        assert!(vault.shares_storage_with(&same));
+        assert!(!vault.shares_storage_with(&other));
+        assert!(matches!(
+            vault.rename("a", &same, "a").await,
+            Err(VaultError::SameKeyError(_))
+        ));
+        assert!(vault.exists("a").await.expect("exists failed"));
+        let team = local_vault_with_prefix(root.path(), Some("team")).await;
+        assert!(matches!(
+            vault.copy("team/b", &team, "b").await,
+            Err(VaultError::SameKeyError(_))
+        ));
+
+        // Different master keys, so the secret is encrypted again for the other vault
+        vault.rename("a", &other, "c").await.expect("rename failed");
+        assert!(!vault.exists("a").await.expect("exists failed"));
+        assert_eq!(
+            other.lookup("c").await.expect("lookup failed").to_string(),
+            "value"
+        );
+        assert_eq!(
+            other.metadata("c").await.expect("metadata failed"),
+            vault.metadata("team/b").await.expect("metadata failed")
+        );
+    }
+
+    #[tokio::test]
+    async fn bulk_store_lookup_and_delete() {
+        let (vault, _root) = local_vault().await;
+        let values: Vec<(String, Vec<u8>)> = (0..5)
+            .map(|i| (format!("bulk/key-{i}"), format!("value-{i}").into_bytes()))
+            .collect();
+        let results = vault.store_many(&values, 2).await;
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+
+        let names = vec![
+            "bulk/key-3".to_string(),
+            "bulk/missing".to_string(),
+            "bulk/key-0".to_string(),
+        ];
+        let results = vault.lookup_many(&names, 2).await;
+        assert_eq!(
+            results.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            names.iter().collect::<Vec<_>>()
+        );
+        assert!(matches!(&results[0].1, Ok(value) if value.as_bytes() == b"value-3"));
+        assert!(matches!(
+            results[1].1,
+            Err(VaultError::KeyDoesNotExistError)
+        ));
+        assert!(matches!(&results[2].1, Ok(value) if value.as_bytes() == b"value-0"));
+
+        let mut duplicated = names.clone();
+        duplicated.push("bulk/key-3".to_string());
+        let results = vault
+            .delete_many_with_results(&duplicated, 2)
+            .await
+            .expect("delete failed");
+        assert_eq!(results.len(), 3);
+        assert!(results[0].1.is_ok());
+        assert!(matches!(
+            &results[1].1,
+            Err(VaultError::S3DeleteObjectKeyMissingError { name }) if name == "bulk/missing"
+        ));
+        assert!(results[2].1.is_ok());
+        assert_eq!(
+            vault.all().await.expect("all failed"),
+            vec!["bulk/key-1", "bulk/key-2", "bulk/key-4"]
+        );
+
+        // Deleting the rest succeeds, but a missing key is reported as an error
+        let mut rest = vault.all().await.expect("all failed");
+        vault.delete_many(&rest).await.expect("delete failed");
+        rest.push("bulk/missing".to_string());
+        assert!(matches!(
+            vault.delete_many(&rest).await,
+            Err(VaultError::DeleteManyError(failed)) if failed.len() == 4
+        ));
+        assert!(vault.all().await.expect("all failed").is_empty());
+    }
+
+    #[test]
+    fn ignore_env_for_separate_vaults() {
+        // Other tests give the storage and key explicitly, so these don't affect them
+        for (name, value) in [
+            ("VAULT_BUCKET", "env-bucket"),
+            ("VAULT_KEY", "env-key"),
+            ("VAULT_LOCAL_DIR", "/tmp/env-vault"),
+            ("VAULT_LOCAL_KEY", "/tmp/env-vault.key"),
+        ] {
+            std::env::set_var(name, value);
+        }
+        let config = VaultConfig {
+            vault_stack: Some("other".to_string()),
+            ..VaultConfig::default()
+        };
+
+        let current = config.clone().with_env_defaults();
+        assert_eq!(current.bucket.as_deref(), Some("env-bucket"));
+        assert_eq!(current.key.as_deref(), Some("env-key"));
+        assert_eq!(current.local_dir.as_deref(), Some("/tmp/env-vault"));
+        assert_eq!(current.local_key.as_deref(), Some("/tmp/env-vault.key"));
+
+        let separate = VaultConfig {
+            ignore_env: true,
+            ..config
+        }
+        .with_env_defaults();
+        assert_eq!(separate.vault_stack.as_deref(), Some("other"));
+        assert!(separate.bucket.is_none());
+        assert!(separate.key.is_none());
+        assert!(separate.local_dir.is_none());
+        assert!(separate.local_key.is_none());
+    }
+
+    #[test]
+    fn common_prefix_of_names() {
+        let names = |names: &[&str]| names.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(common_prefix(&names(&[])), "");
+        assert_eq!(common_prefix(&names(&["prod/db"])), "prod/db");
+        assert_eq!(
+            common_prefix(&names(&["prod/db", "prod/app", "prod/api"])),
+            "prod/"
+        );
+        assert_eq!(common_prefix(&names(&["prod/a", "dev/a"])), "");
+        assert_eq!(common_prefix(&names(&["äö", "äå"])), "ä");
+    }
+
+    #[tokio::test]
+    async fn data_key_cache_shares_keys() {
+        let (vault, root) = local_vault().await;
+        let vault = vault.with_data_key_cache(DataKeyCacheLimits::default());
+        vault.store("a", b"first").await.expect("store failed");
+        vault.store("b", b"second").await.expect("store failed");
+
+        let key_a = vault
+            .storage
+            .get_object(&S3DataKeys::new(&vault.full_key_name("a")).key)
+            .await
+            .expect("get failed");
+        let key_b = vault
+            .storage
+            .get_object(&S3DataKeys::new(&vault.full_key_name("b")).key)
+            .await
+            .expect("get failed");
+        assert_eq!(key_a, key_b);
+
+        // Values are readable without the cache
+        let uncached = local_vault_with_prefix(root.path(), None).await;
+        let value = uncached.lookup("b").await.expect("lookup failed");
+        assert_eq!(value.as_bytes(), b"second");
+        let value = vault.lookup("a").await.expect("lookup failed");
+        assert_eq!(value.as_bytes(), b"first");
+    }
+
+    #[tokio::test]
+    async fn store_stream_and_lookup() {
+        let (vault, _root) = local_vault().await;
+        let data: Vec<u8> = (0..=250).cycle().take(3 * 1024 * 1024 + 100).collect();
+        let metadata = SecretMetadata {
+            owner: Some("team".to_string()),
+            ..SecretMetadata::default()
+        };
+        vault
+            .store_stream("large", data.as_slice(), metadata)
+            .await
+            .expect("store failed");
+
+        let mut output = Vec::new();
+        vault
+            .lookup_to_writer("large", &mut output)
+            .await
+            .expect("lookup failed");
+        assert_eq!(output, data);
+        let value = vault.lookup("large").await.expect("lookup failed");
+        assert_eq!(value.as_bytes(), data);
+        let metadata = vault.metadata("large").await.expect("metadata failed");
+        assert_eq!(metadata.owner.as_deref(), Some("team"));
+
+        // Values stored in one piece can be written too
+        vault.store("small", b"value").await.expect("store failed");
+        let mut output = Vec::new();
+        let metadata = vault
+            .lookup_to_writer("small", &mut output)
+            .await
+            .expect("lookup failed");
+        assert_eq!(output, b"value");
+        assert!(metadata.created.is_some());
+        assert!(matches!(
+            vault.lookup_to_writer("missing", &mut Vec::new()).await,
+            Err(VaultError::KeyDoesNotExistError)
+        ));
+        store_legacy(&vault, "legacy", b"value").await;
+        assert!(matches!(
+            vault.lookup_to_writer("legacy", &mut Vec::new()).await,
+            Err(VaultError::DeprecatedEncryptionError)
+        ));
+
+        // Truncated ciphertext fails instead of returning partial data
+        let cipher = S3DataKeys::new(&vault.full_key_name("large")).cipher;
+        let cipher_text = vault.storage.get_object(&cipher).await.expect("get failed");
+        vault
+            .storage
+            .put_object(&cipher, cipher_text[..2 * (1024 * 1024 + 16)].to_vec())
+            .await
+            .expect("put failed");
+        assert!(matches!(
+            vault.lookup("large").await,
+            Err(VaultError::NonceDecryptError)
+        ));
+        assert!(matches!(
+            vault.lookup_to_writer("large", &mut Vec::new()).await,
+            Err(VaultError::NonceDecryptError)
+        ));
+    }
+
+    #[tokio::test]
+    async fn store_compressed_and_lookup() {
+        let (vault, _root) = local_vault().await;
+        let data = r#"{"cluster": "prod", "server": "https://example.com"}"#.repeat(100);
+        vault
+            .store_compressed(
+                "config",
+                data.as_bytes(),
+                SecretMetadata::default(),
+                Compression::Zstd,
+            )
+            .await
+            .expect("store failed");
+
+        let keys = S3DataKeys::new(&vault.full_key_name("config"));
+        let meta: Meta = serde_json::from_slice(
+            &vault
+                .storage
+                .get_object(&keys.meta)
+                .await
+                .expect("get failed"),
+        )
+        .expect("invalid meta");
+        assert_eq!(meta.alg, ZSTD_ALGORITHM);
+        assert_eq!(meta.compression(), Some(Compression::Zstd));
+        let cipher_text = vault
+            .storage
+            .get_object(&keys.cipher)
+            .await
+            .expect("get failed");
+        assert!(cipher_text.len() < data.len() / 10);
+
+        // Older versions decrypt with the stored nonce without checking the algorithm
+        let data_key = vault
+            .keys
+            .decrypt_data_key(
+                &vault
+                    .storage
+                    .get_object(&keys.key)
+                    .await
+                    .expect("get failed"),
+            )
+            .await
+            .expect("decrypt data key failed");
+        let meta_add = vault
+            .storage
+            .get_object(&keys.meta)
+            .await
+            .expect("get failed");
+        let nonce = base64::engine::general_purpose::STANDARD
+            .decode(&meta.nonce)
+            .expect("invalid nonce");
+        let cipher: AesGcm<Aes256, U12> =
+            AesGcm::new_from_slice(&data_key).expect("invalid data key");
+        assert!(cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &cipher_text,
+                    aad: &meta_add,
+                },
+            )
+            .is_err());
+
+        let value = vault.lookup("config").await.expect("lookup failed");
+        assert!(matches!(value, Value::Utf8(ref text) if *text == data));
+        let mut output = Vec::new();
+        vault
+            .lookup_to_writer("config", &mut output)
+            .await
+            .expect("lookup failed");
+        assert_eq!(output, data.as_bytes());
+
+        // Data that does not compress is stored as is
+        vault
+            .store_compressed("short", b"x", SecretMetadata::default(), Compression::Zstd)
+            .await
+            .expect("store failed");
+        let keys = S3DataKeys::new(&vault.full_key_name("short"));
+        let meta: Meta = serde_json::from_slice(
+            &vault
+                .storage
+                .get_object(&keys.meta)
+                .await
+                .expect("get failed"),
+        )
+        .expect("invalid meta");
+        assert_eq!(meta.alg, "AESGCM");
+        assert_eq!(meta.compression(), None);
+        let value = vault.lookup("short").await.expect("lookup failed");
+        assert_eq!(value.as_bytes(), b"x");
+    }
+
+    #[tokio::test]
+    async fn overwrite_keeps_compression() {
+        let (vault, _root) = local_vault().await;
+        let data = format!(
+            r#"{{"server": "https://example.com", "ca": "{}"}}"#,
+            "A".repeat(4000)
+        );
+        vault
+            .store_compressed(
+                "config",
+                data.as_bytes(),
+                SecretMetadata::default(),
+                Compression::Zstd,
+            )
+            .await
+            .expect("store failed");
+
+        let keys = S3DataKeys::new(&vault.full_key_name("config"));
+        let compression = || async {
+            let meta: Meta = serde_json::from_slice(
+                &vault
+                    .storage
+                    .get_object(&keys.meta)
+                    .await
+                    .expect("get failed"),
+            )
+            .expect("invalid meta");
+            meta.compression()
+        };
+
+        vault
+            .set_field("config", "server", serde_json::json!("https://test.com"))
+            .await
+            .expect("set field failed");
+        assert_eq!(compression().await, Some(Compression::Zstd));
+        let value = vault.lookup("config").await.expect("lookup failed");
+        assert_eq!(
+            value.to_json().expect("invalid json")["server"],
+            "https://test.com"
+        );
+
+        // Editing stores the value with metadata like the edit command
+        vault
+            .store_with_metadata("config", data.as_bytes(), SecretMetadata::default())
+            .await
+            .expect("store failed");
+        assert_eq!(compression().await, Some(Compression::Zstd));
+        assert_eq!(
+            vault
+                .lookup("config")
+                .await
+                .expect("lookup failed")
+                .as_bytes(),
+            data.as_bytes()
+        );
+    }
+
+    #[tokio::test]
+    async fn unsupported_algorithm_fails() {
+        let (vault, _root) = local_vault().await;
+        vault.store("a", b"value").await.expect("store failed");
+        let meta = S3DataKeys::new(&vault.full_key_name("a")).meta;
+        vault
+            .storage
+            .put_object(
+                &meta,
+                br#"{"alg": "AESGCM-FUTURE", "nonce": "AAAAAAAAAAAAAAAA"}"#.to_vec(),
+            )
+            .await
+            .expect("put failed");
+        assert!(matches!(
+            vault.lookup("a").await,
+            Err(VaultError::UnsupportedAlgorithmError(alg)) if alg == "AESGCM-FUTURE"
+        ));
+    }
+
+    #[tokio::test]
+    async fn version_changes_on_store() {
+        let (vault, _root) = local_vault().await;
+        vault.store("a", b"value").await.expect("store failed");
+        let version = vault.version("a").await.expect("version failed");
+        assert_eq!(vault.version("a").await.expect("version failed"), version);
+
+        vault.store("a", b"value").await.expect("store failed");
+        assert_ne!(vault.version("a").await.expect("version failed"), version);
+        assert!(matches!(
+            vault.version("a.aesgcm").await,
+            Err(VaultError::KeyDoesNotExistError)
+        ));
+    }
+
+    #[tokio::test]
+    async fn set_field_keeps_metadata() {
+        let (vault, _root) = local_vault().await;
+        let metadata = SecretMetadata {
+            owner: Some("team".to_string()),
+            ..SecretMetadata::default()
+        };
+        vault
+            .store_with_metadata("db", br#"{"user":"app","password":"old"}"#, metadata)
+            .await
+            .expect("store failed");
+
+        vault
+            .set_field("db", "password", serde_json::json!("new"))
+            .await
+            .expect("set field failed");
+        assert_eq!(
+            vault.lookup("db").await.expect("lookup failed").to_string(),
+            r#"{"user":"app","password":"new"}"#
+        );
+        assert_eq!(
+            vault.metadata("db").await.expect("metadata failed").owner,
+            Some("team".to_string())
+        );
+
+        vault.store("plain", b"text").await.expect("store failed");
+        assert!(matches!(
+            vault.set_field("plain", "a", serde_json::json!(1)).await,
+            Err(VaultError::InvalidJsonError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn diff_by_value() {
+        let (vault, _root) = local_vault().await;
+        let (other, _other_root) = local_vault().await;
+        vault.store("app/same", b"1").await.expect("store failed");
+        vault
+            .store("app/changed", b"2")
+            .await
+            .expect("store failed");
+        vault.store("app/new", b"3").await.expect("store failed");
+        vault.store("other", b"4").await.expect("store failed");
+        other.store("app/same", b"1").await.expect("store failed");
+        other
+            .store("app/changed", b"5")
+            .await
+            .expect("store failed");
+        other.store("app/old", b"6").await.expect("store failed");
+
+        let diff = vault
+            .diff("app/", &other, "app/")
+            .await
+            .expect("diff failed");
+        assert_eq!(diff.only_self, vec!["new"]);
+        assert_eq!(diff.only_other, vec!["old"]);
+        assert_eq!(diff.changed, vec!["changed"]);
+        assert_eq!(diff.unchanged, vec!["same"]);
+
+        let diff = vault.diff("", &other, "").await.expect("diff failed");
+        assert_eq!(diff.only_self, vec!["app/new", "other"]);
+
+        // Different paths in the same vault
+        vault.store("prod/same", b"1").await.expect("store failed");
+        vault
+            .store("prod/changed", b"7")
+            .await
+            .expect("store failed");
+        let diff = vault
+            .diff("app/", &vault, "prod/")
+            .await
+            .expect("diff failed");
+        assert_eq!(diff.only_self, vec!["new"]);
+        assert!(diff.only_other.is_empty());
+        assert_eq!(diff.changed, vec!["changed"]);
+        assert_eq!(diff.unchanged, vec!["same"]);
+    }
+
+    #[tokio::test]
+    async fn diff_and_copy_deprecated_secrets() {
+        let (vault, _root) = local_vault().await;
+        let (other, _other_root) = local_vault().await;
+        store_legacy(&vault, "app/legacy", b"1").await;
+        store_legacy(&vault, "app/changed", b"2").await;
+        store_legacy(&vault, "app/new", b"3").await;
+        store_legacy(&other, "app/legacy", b"1").await;
+        other
+            .store("app/changed", b"4")
+            .await
+            .expect("store failed");
+
+        let diff = vault
+            .diff("app/", &other, "app/")
+            .await
+            .expect("diff failed");
+        assert_eq!(diff.only_self, vec!["new"]);
+        assert!(diff.only_other.is_empty());
+        assert_eq!(diff.changed, vec!["changed"]);
+        assert_eq!(diff.unchanged, vec!["legacy"]);
+
+        // Copying stores the deprecated secret with AES-GCM
+        vault
+            .copy("app/new", &other, "app/new")
+            .await
+            .expect("copy failed");
+        vault
+            .copy("app/changed", &vault, "app/copied")
+            .await
+            .expect("copy failed");
+        assert_eq!(
+            other
+                .lookup("app/new")
+                .await
+                .expect("lookup failed")
+                .as_bytes(),
+            b"3"
+        );
+        assert_eq!(
+            vault
+                .lookup("app/copied")
+                .await
+                .expect("lookup failed")
+                .as_bytes(),
+            b"2"
+        );
+        let diff = vault
+            .diff("app/", &other, "app/")
+            .await
+            .expect("diff failed");
+        assert_eq!(diff.unchanged, vec!["legacy", "new"]);
+    }
+
+    #[test]
+    fn meta_without_metadata_is_compatible() {
+        let meta: Meta = serde_json::from_str(r#"{"alg": "AESGCM", "nonce": "AAAAAAAAAAAAAAAA"}"#)
+            .expect("deserialize failed");
+        assert_eq!(meta.metadata, SecretMetadata::default());
+
+        // Secrets without metadata are stored in the same format as before
+        let json = Meta::aesgcm(&[0; 12], SecretMetadata::default())
+            .to_json()
+            .expect("serialize failed");
+        assert_eq!(json, r#"{"alg":"AESGCM","nonce":"AAAAAAAAAAAAAAAA"}"#);
+    }
+
+    #[tokio::test]
+    async fn all_matching_with_prefix() {
+        let (vault, root) = local_vault().await;
+        let team_vault = local_vault_with_prefix(root.path(), Some("team")).await;
+        for key in [
+            "db-main",
+            "team/prod/db-main",
+            "team/prod/eu/db-replica",
+            "team/prod/api",
+        ] {
+            vault.store(key, b"value").await.expect("store failed");
+        }
+
+        let glob = KeyMatcher::glob("prod/**/db-*").expect("invalid glob");
+        assert_eq!(
+            team_vault.all_matching(&glob).await.expect("list failed"),
+            vec!["prod/db-main", "prod/eu/db-replica"]
+        );
+
+        let regex = KeyMatcher::regex("^db-").expect("invalid regex");
+        assert_eq!(
+            vault.all_matching(&regex).await.expect("list failed"),
+            vec!["db-main"]
+        );
+    }
+
+    #[tokio::test]
+    async fn list_directory_and_tree() {
+        let (vault, root) = local_vault().await;
+        let team_vault = local_vault_with_prefix(root.path(), Some("team")).await;
+        for key in ["top", "team/a", "team/svc/b", "team/svc/c"] {
+            vault.store(key, b"value").await.expect("store failed");
+        }
+
+        let listing = vault.list_directory("").await.expect("list failed");
+        assert_eq!(listing.keys, vec!["top"]);
+        assert_eq!(
+            listing.directories,
+            vec![DirectoryCount {
+                path: "team/".to_string(),
+                count: 3,
+            }]
+        );
+
+        let listing = team_vault
+            .list_directory("svc/")
+            .await
+            .expect("list failed");
+        assert_eq!(listing.keys, vec!["svc/b", "svc/c"]);
+        assert!(listing.directories.is_empty());
+
+        let tree = vault.tree("team/").await.expect("tree failed");
+        assert_eq!(tree.count, 3);
+        assert_eq!(tree.children[0].name, "svc/");
+        assert_eq!(tree.children[0].count, 2);
+    }
+
+    #[test]
+    fn legacy_cipher_matches_python_client() {
+        // Ciphertext produced by the old Python vault `_get_cipher` with key bytes 0..32
+        let mut data = vec![
+            120, 12, 117, 197, 243, 81, 181, 71, 153, 201, 26, 27, 240, 107, 16, 163, 28, 10, 199,
+            251, 99, 92, 57, 49, 41, 131, 136, 237, 67, 248, 105, 115, 1, 134, 64, 40, 242, 16,
+            120, 215, 223, 129, 191,
+        ];
+        let key: Vec<u8> = (0..32).collect();
+        LegacyCipher::new_from_slices(&key, &LEGACY_STATIC_IV)
+            .expect("cipher failed")
+            .apply_keystream(&mut data);
+        assert_eq!(data, b"secret value that spans more than one block");
+    }
+
+    /// Write a secret using the old deprecated AES-CTR format.
+    async fn store_legacy(vault: &Vault, name: &str, value: &[u8]) {
+        let data_key = vault
+            .keys
+            .generate_data_key()
+            .await
+            .expect("data key failed");
+        let mut cipher_text = value.to_vec();
+        LegacyCipher::new_from_slices(&data_key.plaintext, &LEGACY_STATIC_IV)
+            .expect("cipher failed")
+            .apply_keystream(&mut cipher_text);
+        let keys = S3DataKeys::new(&vault.full_key_name(name));
+        vault
+            .storage
+            .put_object(&keys.key, data_key.ciphertext)
+            .await
+            .expect("put failed");
+        vault
+            .storage
+            .put_object(&keys.legacy_cipher, cipher_text)
+            .await
+            .expect("put failed");
+    }
+
+    #[tokio::test]
+    async fn recrypt_deprecated_secret() {
+        let (vault, _root) = local_vault().await;
+        store_legacy(&vault, "old", b"legacy value").await;
+
+        assert!(matches!(
+            vault.lookup("old").await,
+            Err(VaultError::DeprecatedEncryptionError)
+        ));
+        assert_eq!(
+            vault.all_deprecated().await.expect("list failed"),
+            vec!["old"]
+        );
+
+        assert!(vault.recrypt("old").await.expect("recrypt failed"));
+        let value = vault.lookup("old").await.expect("lookup failed");
+        assert!(matches!(value, Value::Utf8(ref text) if text == "legacy value"));
+        assert!(vault
+            .all_deprecated()
+            .await
+            .expect("list failed")
+            .is_empty());
+        assert!(!vault
+            .storage
+            .object_exists("old.encrypted")
+            .await
+            .expect("exists failed"));
+
+        // Already using AES-GCM
+        assert!(!vault.recrypt("old").await.expect("recrypt failed"));
+    }
+}